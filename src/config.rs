@@ -0,0 +1,48 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::Path;
+
+// --config: turns a toml file whose keys are the argument names (server_address, key_size,
+// ...) into command line arguments. keys already given on the command line or through a
+// MEMCACHED_BENCH_* environment variable are skipped, so both take precedence
+pub fn config_args(path: &Path, matches: &ArgMatches) -> Result<Vec<OsString>, Box<dyn Error>> {
+    let config: toml::Table = std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut args = vec![];
+    for (key, value) in config.iter() {
+        if key == "config" || matches.try_get_raw(key).is_err() {
+            return Err(format!("{}: unknown key {}", path.display(), key).into());
+        }
+        if let Some(ValueSource::CommandLine | ValueSource::EnvVariable) = matches.value_source(key)
+        {
+            continue;
+        }
+
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                // flags take no value, false is their default
+                toml::Value::Boolean(true) => args.push(flag.clone().into()),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(s) => args.push(format!("{}={}", flag, s).into()),
+                toml::Value::Integer(_) | toml::Value::Float(_) => {
+                    args.push(format!("{}={}", flag, value).into())
+                }
+                _ => {
+                    return Err(
+                        format!("{}: unsupported value for key {}", path.display(), key).into(),
+                    )
+                }
+            }
+        }
+    }
+    Ok(args)
+}
@@ -0,0 +1,51 @@
+use hdrhistogram::serialization::interval_log::{IntervalLogWriterBuilder, Tag};
+use hdrhistogram::serialization::V2DeflateSerializer;
+use hdrhistogram::Histogram;
+use std::error::Error;
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+// one pass of --output-format hdr
+pub struct Interval {
+    // the operation of the pass
+    pub tag: String,
+    // since the start of the log
+    pub start: Duration,
+    pub duration: Duration,
+    pub histogram: Histogram<u64>,
+}
+
+// latencies in nanoseconds, three significant digits and as wide as the slowest reply
+pub fn histogram(samples: &[u64]) -> Result<Histogram<u64>, Box<dyn Error>> {
+    let mut histogram = Histogram::new(3)?;
+    for &ns in samples {
+        histogram.record(ns)?;
+    }
+    Ok(histogram)
+}
+
+// --output-format hdr: every pass as a compressed histogram line of an HdrHistogram interval
+// log, the format HistogramLogProcessor and the HdrHistogram plotter read. timestamps are
+// relative to the BaseTime, the max column is in milliseconds
+pub fn write_log(
+    out: &mut impl Write,
+    start: SystemTime,
+    intervals: &[Interval],
+) -> Result<(), Box<dyn Error>> {
+    let mut serializer = V2DeflateSerializer::new();
+    let mut log = IntervalLogWriterBuilder::new()
+        .add_comment("memcached_benchmark latencies in nanoseconds")
+        .with_start_time(start)
+        .with_base_time(start)
+        .with_max_value_divisor(1_000_000.0)
+        .begin_log_with(out, &mut serializer)?;
+    for interval in intervals {
+        log.write_histogram(
+            &interval.histogram,
+            interval.start,
+            interval.duration,
+            Tag::new(&interval.tag),
+        )?;
+    }
+    Ok(())
+}
@@ -1,92 +1,1049 @@
-use clap::{Parser, ValueEnum};
+mod config;
+mod hdr;
+mod metrics;
+mod output;
+mod response;
+mod sampler;
+mod sizes;
+mod srv;
+mod stats;
+mod table;
+mod tls;
+mod values;
+mod writer;
+
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
 use memcache::MemcacheError;
+use output::{
+    ConsistencySummary, EvictionSummary, IntervalSample, KeyBoundaryResult, MemoryDelta,
+    OperationSummary, ProtocolComparison, ProtocolRuns, Report, RunResult, RunsSummary,
+    ServerStatsDelta, Summary, CSV_HEADER,
+};
 use rand::distributions::{Alphanumeric, DistString};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use response::{frame_header, parse_blocks, parse_udp_response};
+use sampler::{by_key_size, new_key_sampler, new_trace_sampler, with_hot_keys, KeyDistribution};
+use sizes::{SizeDistribution, SizeSampler};
+use socket2::{Domain, SockRef, Socket, TcpKeepalive, Type};
+use stats::{
+    BenchmarkStats, ByteCounter, ErrorKind, HitCounter, LatencyRecorder, PacketLossTracker,
+};
 use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::vec;
-use tokio::net::UdpSocket;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket, UnixStream};
 use tokio::sync::mpsc;
-use tokio::time::timeout;
+use tokio::task::JoinHandle;
+use tokio::time::error::Elapsed;
+use tokio::time::{timeout, timeout_at, MissedTickBehavior};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_util::sync::CancellationToken;
+use values::{new_value_generator, IntegrityHash, ValueGenerator, ValuePattern};
+use writer::Writer;
 
+use std::sync::Mutex;
 use std::{collections::HashMap, sync::Arc};
 
 const NUM_ENTRIES: usize = 10000;
+// longest key the memcached protocol accepts
+const MAX_KEY_LENGTH: usize = 250;
+const KEY_BOUNDARY_LENGTHS: RangeInclusive<usize> = MAX_KEY_LENGTH - 5..=MAX_KEY_LENGTH + 5;
+const KEY_BOUNDARY_VALUE: &[u8] = b"key-boundary-test";
+// memcached's default -I item size limit, normal and lognormal value sizes stay below it
+const MAX_VALUE_LENGTH: usize = 1024 * 1024;
 const BUFFER_SIZE: usize = 1500;
+const UDP_HEADER_SIZE: usize = 8;
+// memcached never sends a UDP datagram larger than this, frame header included
+const UDP_MAX_DATAGRAM: usize = 1400;
+const COUNTER_INITIAL_VALUE: u64 = 1_000_000;
+const COUNTER_DELTA: u64 = 1;
+// what every prepend/append request adds in front of/behind the stored value
+const PREPEND_DATA: &str = "prepend:";
+const APPEND_DATA: &str = ":append";
+const PROGRESS_INTERVAL: u64 = 1000;
+// evicted or expired unfetched items over this fraction of the sets of a run get a warning
+const ITEM_LOSS_WARN_RATIO: f64 = 0.01;
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+// server counters compared before and after the benchmark passes
+const STATS_DELTA_FIELDS: [&str; 8] = [
+    "cmd_get",
+    "cmd_set",
+    "get_hits",
+    "get_misses",
+    "evictions",
+    "expired_unfetched",
+    "bytes_written",
+    "bytes_read",
+];
+// server counters logged every --stats-interval-s, curr_items and bytes are gauges
+const STATS_POLL_FIELDS: [&str; 7] = [
+    "cmd_get",
+    "cmd_set",
+    "get_hits",
+    "get_misses",
+    "evictions",
+    "curr_items",
+    "bytes",
+];
+// the first memcached release that knows each command, older ones answer it with ERROR
+const OPERATION_MIN_VERSIONS: [(Operation, (u32, u32, u32)); 4] = [
+    (Operation::Gets, (1, 2, 4)),
+    (Operation::Cas, (1, 2, 4)),
+    (Operation::Prepend, (1, 2, 4)),
+    (Operation::Append, (1, 2, 4)),
+];
+// udp replies of older releases were unreliable
+const UDP_MIN_VERSION: (u32, u32, u32) = (1, 4, 0);
+// the memcache crate speaks the binary protocol over every transport, udp included, so the
+// dataset needs a release that knows it. older ones only speak ascii
+const BINARY_PROTOCOL_MIN_VERSION: (u32, u32, u32) = (1, 3, 0);
+const CHANNEL_SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+const ERROR_RATE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
 enum Protocol {
     Udp,
     Tcp,
+    // a local server's unix domain socket, see --unix-socket
+    Unix,
+    // tcp wrapped in TLS, e.g. for a server behind stunnel or envoy, see --tls
+    Tls,
+}
+
+// numeric keys are the zero-padded integers 1..=NUM_ENTRIES, --key-size digits wide
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum KeyFormat {
+    Random,
+    Numeric,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Text,
+    Json,
+    Csv,
+    Ndjson,
+    Hdr,
+}
+
+impl OutputFormat {
+    // formats read by a person as the run goes, with a header and a progress bar
+    fn is_human(self) -> bool {
+        matches!(self, OutputFormat::Table | OutputFormat::Text)
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum Operation {
+    Get,
+    Set,
+    Delete,
+    Incr,
+    Decr,
+    Cas,
+    Prepend,
+    Append,
+    Replace,
+    Add,
+    // keeps the cas token of every reply for a later cas pass
+    Gets,
+    // get with --batch-size keys per request
+    #[value(skip)]
+    MultiGet,
 }
 
-#[derive(Parser)]
+// every option can also be set through a MEMCACHED_BENCH_* environment variable named after
+// it (MEMCACHED_BENCH_SERVER for --server-address), the command line overrides it
+#[derive(Parser, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(short, long, default_value = "127.0.0.1")]
-    server_address: String,
+    /// read defaults for any of these options from a toml file with the argument names as
+    /// keys, e.g. key_size = 32; the command line and the environment take precedence
+    #[arg(long, env = "MEMCACHED_BENCH_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// repeat to benchmark several servers, every server gets the whole dataset. every
+    /// benchmark thread has a connection (with -l udp a socket) to each server and sends its
+    /// requests to them round-robin. MEMCACHED_BENCH_SERVER takes a comma separated list
+    #[arg(
+        short,
+        long,
+        default_value = "127.0.0.1",
+        value_delimiter = ',',
+        env = "MEMCACHED_BENCH_SERVER"
+    )]
+    server_address: Vec<String>,
 
-    #[arg(short, long, default_value = "11211")]
+    #[arg(short, long, default_value = "11211", env = "MEMCACHED_BENCH_PORT")]
     port: String,
 
+    /// take the servers from the SRV records of _memcache._tcp.<name> (_udp with -l udp)
+    /// instead of --server-address and --port, e.g. a kubernetes headless service. each
+    /// server gets a share of the requests in proportion to its record weight
+    #[arg(long, env = "MEMCACHED_BENCH_SRV_RECORD")]
+    srv_record: Option<String>,
+
     /// key size to generate random memcached key
-    #[arg(short, long, default_value = "16")]
+    #[arg(short, long, default_value = "16", env = "MEMCACHED_BENCH_KEY_SIZE")]
     key_size: usize,
 
+    /// with --key-size-max, draw every key size uniformly from this range instead of --key-size
+    #[arg(long, env = "MEMCACHED_BENCH_KEY_SIZE_MIN")]
+    key_size_min: Option<usize>,
+
+    #[arg(long, env = "MEMCACHED_BENCH_KEY_SIZE_MAX")]
+    key_size_max: Option<usize>,
+
+    /// draw key sizes uniformly from --key-size-min/max, or around --key-size-mean with a
+    /// normal or lognormal distribution clamped to the memcached key limit. every request draws
+    /// a size and picks a dataset key of the nearest size with --key-distribution
+    #[arg(long, value_enum, default_value_t = SizeDistribution::Uniform, env = "MEMCACHED_BENCH_KEY_SIZE_DISTRIBUTION")]
+    key_size_distribution: SizeDistribution,
+
+    /// mean key size of a normal or lognormal --key-size-distribution, --key-size when left out
+    #[arg(long, env = "MEMCACHED_BENCH_KEY_SIZE_MEAN")]
+    key_size_mean: Option<f64>,
+
+    /// standard deviation of the key sizes of a normal or lognormal --key-size-distribution
+    #[arg(long, env = "MEMCACHED_BENCH_KEY_SIZE_STDDEV")]
+    key_size_stddev: Option<f64>,
+
+    /// namespace put in front of every generated key, so concurrent runs don't collide
+    #[arg(long, default_value = "", env = "MEMCACHED_BENCH_KEY_PREFIX")]
+    key_prefix: String,
+
+    /// random alphanumeric keys, or zero-padded sequential integers
+    #[arg(long, value_enum, default_value_t = KeyFormat::Random, env = "MEMCACHED_BENCH_KEY_FORMAT")]
+    key_format: KeyFormat,
+
+    /// draw more random keys until the dataset has no duplicates
+    #[arg(long, default_value = "false", env = "MEMCACHED_BENCH_DEDUPLICATE")]
+    deduplicate: bool,
+
+    /// load the dataset from newline-delimited key<TAB>value pairs instead of generating it
+    #[arg(long, env = "MEMCACHED_BENCH_DATASET_FILE")]
+    dataset_file: Option<PathBuf>,
+
     /// value size to generate random memcached value
-    #[arg(short, long, default_value = "32")]
+    #[arg(short, long, default_value = "32", env = "MEMCACHED_BENCH_VALUE_SIZE")]
     value_size: usize,
 
+    /// with --value-size-max, draw every value size uniformly from this range instead of
+    /// --value-size
+    #[arg(long, env = "MEMCACHED_BENCH_VALUE_SIZE_MIN")]
+    value_size_min: Option<usize>,
+
+    #[arg(long, env = "MEMCACHED_BENCH_VALUE_SIZE_MAX")]
+    value_size_max: Option<usize>,
+
+    /// draw value sizes uniformly from --value-size-min/max, or around --value-size-mean with
+    /// a normal or lognormal distribution; lognormal gives the small median and long tail of
+    /// web cache objects. values are drawn once per dataset entry, when the dataset is generated
+    #[arg(long, value_enum, default_value_t = SizeDistribution::Uniform, env = "MEMCACHED_BENCH_VALUE_SIZE_DISTRIBUTION")]
+    value_size_distribution: SizeDistribution,
+
+    /// mean value size of a normal or lognormal --value-size-distribution, --value-size when
+    /// left out
+    #[arg(long, env = "MEMCACHED_BENCH_VALUE_SIZE_MEAN")]
+    value_size_mean: Option<f64>,
+
+    /// standard deviation of the value sizes of a normal or lognormal --value-size-distribution
+    #[arg(long, env = "MEMCACHED_BENCH_VALUE_SIZE_STDDEV")]
+    value_size_stddev: Option<f64>,
+
     /// verify the value after get command
-    #[arg(short = 'd', long, default_value = "false")]
+    #[arg(
+        short = 'd',
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_VALIDATE"
+    )]
     validate: bool,
 
+    /// append a hash of every value to it when the dataset is stored, and check it on every
+    /// --validate get to catch partial corruption
+    #[arg(long, value_enum, env = "MEMCACHED_BENCH_INTEGRITY_HASH")]
+    integrity_hash: Option<IntegrityHash>,
+
     /// number of test entries to generate
-    #[arg(short, long, default_value = "100000")]
+    #[arg(short, long, default_value = "100000", env = "MEMCACHED_BENCH_NUMS")]
     nums: usize,
 
     // number of threads to run
-    #[arg(short, long, default_value = "4")]
+    #[arg(short, long, default_value = "4", env = "MEMCACHED_BENCH_THREADS")]
     threads: usize,
 
     /// udp or tcp protocol for memcached
-    #[arg(short = 'l', long, default_value_t = Protocol::Udp , value_enum)]
+    #[arg(short = 'l', long, default_value_t = Protocol::Udp , value_enum, env = "MEMCACHED_BENCH_PROTOCOL")]
     protocol: Protocol,
+
+    /// SO_SNDBUF of every UDP socket in bytes, the kernel may cap (or on linux double) it.
+    /// raise it when sends fail with ENOBUFS at high rates or with large values
+    #[arg(long, env = "MEMCACHED_BENCH_UDP_SEND_BUFFER_BYTES")]
+    udp_send_buffer_bytes: Option<usize>,
+
+    /// SO_RCVBUF of every UDP socket in bytes, the kernel may cap (or on linux double) it
+    #[arg(long, env = "MEMCACHED_BENCH_UDP_RECV_BUFFER_BYTES")]
+    udp_recv_buffer_bytes: Option<usize>,
+
+    /// enable TCP keep-alive on the benchmark connections, probing after this many idle
+    /// seconds, so middleboxes keep idle ones open. the long-lived clients that store the
+    /// dataset and read the server stats get a version request as often instead, the
+    /// memcache crate does not expose their sockets
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_TCP_KEEPALIVE_S")]
+    tcp_keepalive_s: Option<u64>,
+
+    /// UDP sockets of every benchmark thread, its requests take turns on them. more source
+    /// ports spread the load over more of the server's UDP worker threads
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_UDP_SOCKETS")]
+    udp_sockets: u64,
+
+    /// benchmark a local server over its unix domain socket at this path, implies -l unix
+    #[arg(long, env = "MEMCACHED_BENCH_UNIX_SOCKET")]
+    unix_socket: Option<PathBuf>,
+
+    /// connect over TLS, implies -l tls
+    #[arg(long, default_value = "false", env = "MEMCACHED_BENCH_TLS")]
+    tls: bool,
+
+    /// PEM certificates to verify the server with, the webpki roots without it
+    #[arg(long, env = "MEMCACHED_BENCH_TLS_CA_CERT")]
+    tls_ca_cert: Option<PathBuf>,
+
+    /// PEM certificate chain for client authentication, with --tls-client-key
+    #[arg(long, env = "MEMCACHED_BENCH_TLS_CLIENT_CERT")]
+    tls_client_cert: Option<PathBuf>,
+
+    /// PEM private key of --tls-client-cert
+    #[arg(long, env = "MEMCACHED_BENCH_TLS_CLIENT_KEY")]
+    tls_client_key: Option<PathBuf>,
+
+    /// operations to benchmark, run one after another
+    #[arg(short, long, value_delimiter = ',', default_values_t = [Operation::Set, Operation::Get], value_enum, env = "MEMCACHED_BENCH_OPERATION")]
+    operation: Vec<Operation>,
+
+    /// fraction of requests in the get pass that are GETs, the rest are SETs
+    #[arg(long, default_value = "1.0", value_parser = parse_ratio, env = "MEMCACHED_BENCH_READ_RATIO")]
+    read_ratio: f64,
+
+    /// table or text lines for humans, json for a single object at the end of the run, csv for
+    /// one row per run, ndjson for a json line per --interval-ms sample followed by a summary line,
+    /// hdr for an HdrHistogram interval log with one histogram per pass
+    #[arg(long, default_value_t = OutputFormat::Table, value_enum, env = "MEMCACHED_BENCH_OUTPUT_FORMAT")]
+    output_format: OutputFormat,
+
+    /// cap on the total request rate in ops/sec across all threads, 0 for unlimited
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_RATE")]
+    rate: u64,
+
+    /// raise the request rate linearly from --ramp-start-rate to --rate over this many
+    /// seconds at the start of each pass, then hold it
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_RAMP_DURATION_S")]
+    ramp_duration_s: u64,
+
+    /// total request rate in ops/sec the ramp starts from
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_RAMP_START_RATE")]
+    ramp_start_rate: u64,
+
+    /// requests sent before the timer starts, their results are discarded
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_WARMUP_OPS")]
+    warmup_ops: usize,
+
+    /// send the --warmup-ops of each pass from this many threads of their own, and print
+    /// their throughput, instead of warming up every benchmark connection
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_WARMUP_THREADS")]
+    warmup_threads: Option<u64>,
+
+    /// requests per thread sent after the timed part of each pass and left out of the
+    /// results, so no stale reply of a pass is read by the next one
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_COOLDOWN_OPS")]
+    cooldown_ops: usize,
+
+    /// generate each request only once the previous reply arrived, without the queue in
+    /// between, for the round trip time of a single outstanding request
+    #[arg(long, default_value = "false", env = "MEMCACHED_BENCH_CLOSED_LOOP")]
+    closed_loop: bool,
+
+    /// pause after queueing each request, 0 sends back to back.
+    /// tokio timers tick in milliseconds, so shorter pauses round up
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_THINK_TIME_US")]
+    think_time_us: u64,
+
+    /// spread each pause uniformly over think time ± jitter microseconds
+    #[arg(
+        long,
+        default_value = "0",
+        env = "MEMCACHED_BENCH_THINK_TIME_JITTER_US"
+    )]
+    think_time_jitter_us: u64,
+
+    /// send this many requests back to back, then pause for --burst-interval-ms. 0 disables
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_BURST_SIZE")]
+    burst_size: usize,
+
+    /// pause between two bursts
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_BURST_INTERVAL_MS")]
+    burst_interval_ms: u64,
+
+    /// how keys are picked for each request
+    #[arg(long, default_value_t = KeyDistribution::Uniform, value_enum, env = "MEMCACHED_BENCH_KEY_DISTRIBUTION")]
+    key_distribution: KeyDistribution,
+
+    /// skew of the zipf key distribution, larger is more concentrated on hot keys
+    #[arg(long, default_value = "1.0", env = "MEMCACHED_BENCH_ZIPF_EXPONENT")]
+    zipf_exponent: f64,
+
+    /// replay the keys of this file, one per line, in order instead of sampling them.
+    /// every thread starts at the top and wraps around at the end
+    #[arg(long, env = "MEMCACHED_BENCH_TRACE_FILE")]
+    trace_file: Option<PathBuf>,
+
+    /// fraction of the key space that is hot, see --hot-key-ratio
+    #[arg(long, default_value = "0.0", value_parser = parse_ratio, env = "MEMCACHED_BENCH_HOT_KEY_FRACTION")]
+    hot_key_fraction: f64,
+
+    /// fraction of the requests that go to the hot keys, picked uniformly among them
+    #[arg(long, default_value = "0.0", value_parser = parse_ratio, env = "MEMCACHED_BENCH_HOT_KEY_RATIO")]
+    hot_key_ratio: f64,
+
+    /// only request the first this many keys of the (sorted) dataset. with a server whose
+    /// memory holds fewer keys than the dataset, the hit rate follows the working set size
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_WORKING_SET_SIZE")]
+    working_set_size: Option<u64>,
+
+    /// seed for the dataset and key selection, a random one is picked and printed if unset
+    #[arg(long, env = "MEMCACHED_BENCH_SEED")]
+    seed: Option<u64>,
+
+    /// expiration time in seconds for stored keys, 0 never expires
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_TTL")]
+    ttl: u32,
+
+    /// spread each key's ttl uniformly over ttl ± jitter seconds
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_TTL_JITTER")]
+    ttl_jitter: u32,
+
+    /// flags stored with every set, add, replace and cas, where clients keep e.g. the
+    /// serialization or compression of a value
+    #[arg(long, default_value_t = 0, env = "MEMCACHED_BENCH_SET_FLAGS")]
+    set_flags: u32,
+
+    /// number of GETs written back to back before reading their replies, TCP and unix sockets only
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_PIPELINE_DEPTH")]
+    pipeline_depth: u64,
+
+    /// requests each sender may queue ahead of its socket task before it has to wait
+    #[arg(long, default_value = "100000", value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_CHANNEL_CAPACITY")]
+    channel_capacity: u64,
+
+    /// how long to wait for each reply before counting the request as timed out
+    #[arg(long, default_value = "500", env = "MEMCACHED_BENCH_RECV_TIMEOUT_MS")]
+    recv_timeout_ms: u64,
+
+    /// number of keys fetched by each GET request, more than 1 sends multi-key gets
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_BATCH_SIZE")]
+    batch_size: u64,
+
+    /// run each pass for this many seconds instead of a fixed count, --nums is then ignored
+    #[arg(long, env = "MEMCACHED_BENCH_DURATION")]
+    duration: Option<u64>,
+
+    /// repeat every pass this many times and report the mean and standard deviation of
+    /// throughput, p99 latency and error rate across the runs
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..), env = "MEMCACHED_BENCH_RUNS")]
+    runs: u64,
+
+    /// run everything over udp, then again over tcp against the same dataset, and compare
+    /// throughput, p99 latency and error rate of the two
+    #[arg(
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_COMPARE_PROTOCOLS"
+    )]
+    compare_protocols: bool,
+
+    /// resend a request this many times when sending it fails
+    #[arg(long, default_value_t = 0, env = "MEMCACHED_BENCH_MAX_RETRIES")]
+    max_retries: u32,
+
+    /// exit with code 1 when more than this fraction of requests fail, e.g. 0.01
+    #[arg(long, value_parser = parse_ratio, env = "MEMCACHED_BENCH_FAIL_ON_ERROR_RATE")]
+    fail_on_error_rate: Option<f64>,
+
+    /// stop the benchmark as soon as more than this fraction of requests fail, checked every
+    /// second, and exit with code 1
+    #[arg(long, value_parser = parse_ratio, env = "MEMCACHED_BENCH_ABORT_ON_ERROR_RATE")]
+    abort_on_error_rate: Option<f64>,
+
+    /// exit with code 2 when the p99 latency exceeds this many milliseconds
+    #[arg(long, env = "MEMCACHED_BENCH_SLA_P99_MS")]
+    sla_p99_ms: Option<f64>,
+
+    /// exit with code 3 when the p99.9 latency exceeds this many milliseconds
+    #[arg(long, env = "MEMCACHED_BENCH_SLA_P999_MS")]
+    sla_p999_ms: Option<f64>,
+
+    /// store this string as the value of every key instead of random values, overrides --value-size
+    #[arg(long, env = "MEMCACHED_BENCH_FIXED_VALUE")]
+    fixed_value: Option<String>,
+
+    /// how generated values are filled, compressible repeats an 8-byte chunk and
+    /// random-bytes is incompressible binary data
+    #[arg(long, value_enum, default_value_t = ValuePattern::Random, env = "MEMCACHED_BENCH_VALUE_PATTERN")]
+    value_pattern: ValuePattern,
+
+    /// check the request id of every UDP reply and count dropped, late and out-of-order replies
+    #[arg(
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_VALIDATE_SEQUENCE"
+    )]
+    validate_sequence: bool,
+
+    /// after each get pass, read back every key of the dataset once and compare its value
+    #[arg(
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_CONSISTENCY_CHECK"
+    )]
+    consistency_check: bool,
+
+    /// instead of benchmarking, set and get one key of every length from 245 to 255 bytes
+    /// and report which lengths the server takes, around its 250 byte key limit
+    #[arg(
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_KEY_BOUNDARY_TEST"
+    )]
+    key_boundary_test: bool,
+
+    /// report the evictions of every server during the run, per second and per set
+    #[arg(
+        long,
+        default_value = "false",
+        env = "MEMCACHED_BENCH_MEASURE_EVICTIONS"
+    )]
+    measure_evictions: bool,
+
+    /// add keys one byte longer than --key-size, so they are never in the dataset and get stored
+    #[arg(long, default_value = "false", env = "MEMCACHED_BENCH_ADD_NEW_KEYS")]
+    add_new_keys: bool,
+
+    /// write the results to this file instead of stdout
+    #[arg(long, env = "MEMCACHED_BENCH_OUTPUT_FILE")]
+    output_file: Option<PathBuf>,
+
+    /// append to --output-file instead of truncating it, csv only writes its header into an
+    /// empty file
+    #[arg(long, default_value = "false", env = "MEMCACHED_BENCH_APPEND")]
+    append: bool,
+
+    /// table latencies at or above this are yellow, below it green
+    #[arg(long, default_value = "1.0", env = "MEMCACHED_BENCH_LATENCY_WARN_MS")]
+    latency_warn_ms: f64,
+
+    /// table latencies at or above this are red
+    #[arg(
+        long,
+        default_value = "10.0",
+        env = "MEMCACHED_BENCH_LATENCY_CRITICAL_MS"
+    )]
+    latency_critical_ms: f64,
+
+    /// serve live prometheus metrics over http on this port at /metrics
+    #[arg(long, env = "MEMCACHED_BENCH_METRICS_PORT")]
+    metrics_port: Option<u16>,
+
+    /// print the throughput of the running pass to stderr every this many milliseconds, 0 disables
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_INTERVAL_MS")]
+    interval_ms: u64,
+
+    /// sleep this many milliseconds after every get that misses, standing in for the backend
+    /// fetch a miss triggers. the penalty counts toward the latency and holds up the next
+    /// request of the connection, it does not apply with --pipeline-depth
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_MISS_PENALTY_MS")]
+    miss_penalty_ms: u64,
+
+    /// log the change of the server stats of every server every this many seconds during
+    /// the benchmark, 0 disables
+    #[arg(long, default_value = "0", env = "MEMCACHED_BENCH_STATS_INTERVAL_S")]
+    stats_interval_s: u64,
+}
+
+impl Cli {
+    fn key_sizes(&self) -> RangeInclusive<usize> {
+        size_range(self.key_size, self.key_size_min, self.key_size_max)
+    }
+
+    fn value_sizes(&self) -> RangeInclusive<usize> {
+        size_range(self.value_size, self.value_size_min, self.value_size_max)
+    }
+
+    // normal and lognormal sizes stay within 1..=250, less whatever --key-prefix takes
+    fn key_size_distribution(&self) -> Result<SizeSampler, String> {
+        size_sampler(
+            "key",
+            self.key_size_distribution,
+            self.key_sizes(),
+            1..=MAX_KEY_LENGTH.saturating_sub(self.key_prefix.len()).max(1),
+            self.key_size_mean.unwrap_or(self.key_size as f64),
+            self.key_size_stddev,
+        )
+    }
+
+    fn value_size_distribution(&self) -> Result<SizeSampler, String> {
+        size_sampler(
+            "value",
+            self.value_size_distribution,
+            self.value_sizes(),
+            0..=MAX_VALUE_LENGTH,
+            self.value_size_mean.unwrap_or(self.value_size as f64),
+            self.value_size_stddev,
+        )
+    }
+}
+
+// a bound left out follows the fixed size, or the other bound when that is past it
+fn size_range(size: usize, min: Option<usize>, max: Option<usize>) -> RangeInclusive<usize> {
+    let min_size = min.unwrap_or(size.min(max.unwrap_or(size)));
+    let max_size = max.unwrap_or(size.max(min_size));
+    min_size..=max_size
+}
+
+// uniform draws from --{name}-size-min/max, normal and lognormal are clamped to `limit`
+fn size_sampler(
+    name: &str,
+    distribution: SizeDistribution,
+    uniform: RangeInclusive<usize>,
+    limit: RangeInclusive<usize>,
+    mean: f64,
+    stddev: Option<f64>,
+) -> Result<SizeSampler, String> {
+    match (distribution, stddev) {
+        (SizeDistribution::Uniform, _) => SizeSampler::new(distribution, uniform, mean, 0.0),
+        (_, Some(stddev)) => SizeSampler::new(distribution, limit, mean, stddev),
+        (_, None) => Err(format!(
+            "a normal or lognormal --{}-size-distribution needs --{}-size-stddev",
+            name, name
+        )),
+    }
 }
 
-fn generate_random_str(len: usize) -> String {
-    Alphanumeric.sample_string(&mut rand::thread_rng(), len)
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(format!("{} is not in 0.0..=1.0", ratio));
+    }
+    Ok(ratio)
+}
+
+fn generate_random_str(rng: &mut impl Rng, len: usize) -> String {
+    Alphanumeric.sample_string(rng, len)
 }
 
 fn generate_memcached_test_dict(
-    key_size: usize,
-    value_size: usize,
+    rng: &mut impl Rng,
+    args: &Cli,
+    key_sizes: &SizeSampler,
+    value_sizes: &SizeSampler,
+    values: &mut dyn ValueGenerator,
     nums: usize,
-) -> HashMap<String, String> {
-    // random generate dict for memcached test
-    (0..nums)
-        .map(|_| {
-            (
-                generate_random_str(key_size),
-                generate_random_str(value_size),
+) -> HashMap<String, Vec<u8>> {
+    // random generate dict for memcached test. --deduplicate gives up after ten draws per
+    // entry, so a key space smaller than `nums` cannot loop forever
+    let attempts = if args.deduplicate { nums * 10 } else { nums };
+    let mut test_dict = HashMap::with_capacity(nums);
+    let mut generated = 0;
+    while generated < attempts && test_dict.len() < nums {
+        generated += 1;
+        let key_size = key_sizes.sample(rng);
+        let key = match args.key_format {
+            KeyFormat::Random => generate_random_str(rng, key_size),
+            KeyFormat::Numeric => format!("{:0width$}", generated, width = key_size),
+        };
+        let value_size = value_sizes.sample(rng);
+        test_dict.insert(
+            format!("{}{}", args.key_prefix, key),
+            values.generate(rng, value_size),
+        );
+    }
+
+    if generated > test_dict.len() {
+        tracing::warn!(
+            discarded = generated - test_dict.len(),
+            dataset_size = test_dict.len(),
+            "duplicate keys discarded"
+        );
+    }
+    test_dict
+}
+
+// --dataset-file: one `key\tvalue` pair per line, blank lines are skipped. values are taken
+// as raw bytes, only the keys have to be utf-8
+fn load_memcached_test_dict(path: &Path) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
+    let mut test_dict = HashMap::new();
+    for (i, line) in std::fs::read(path)?.split(|&b| b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let tab = line
+            .iter()
+            .position(|&b| b == b'\t')
+            .ok_or_else(|| format!("{}:{}: expected key<TAB>value", path.display(), i + 1))?;
+        let key = std::str::from_utf8(&line[..tab])
+            .map_err(|e| format!("{}:{}: key is not utf-8: {}", path.display(), i + 1, e))?;
+        test_dict.insert(key.to_string(), line[tab + 1..].to_vec());
+    }
+    Ok(test_dict)
+}
+
+// --trace-file: one key per line, blank lines are skipped. every key has to be in the dataset
+fn load_trace(
+    path: &Path,
+    test_dict: &HashMap<String, Vec<u8>>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut trace = vec![];
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        if !test_dict.contains_key(line) {
+            return Err(format!(
+                "{}:{}: key {} is not in the dataset",
+                path.display(),
+                i + 1,
+                line
             )
-        })
-        .collect()
+            .into());
+        }
+        trace.push(line.to_string());
+    }
+    if trace.is_empty() {
+        return Err(format!("{}: the trace is empty", path.display()).into());
+    }
+    Ok(trace)
+}
+
+// keys that never expire stay that way, jittered ttls never drop below one second
+fn sample_ttl(rng: &mut impl Rng, ttl: u32, jitter: u32) -> u32 {
+    if ttl == 0 || jitter == 0 {
+        return ttl;
+    }
+    let low = ttl.saturating_sub(jitter).max(1);
+    rng.gen_range(low..=ttl.saturating_add(jitter))
+}
+
+// --ramp-duration-s: gap between two requests of one thread, `elapsed` into the ramp
+fn ramp_period(args: &Cli, elapsed: Duration) -> Duration {
+    let progress = (elapsed.as_secs_f64() / args.ramp_duration_s as f64).min(1.0);
+    let (start, end) = (args.ramp_start_rate as f64, args.rate as f64);
+    let rate = start + (end - start) * progress;
+    // at least one request a second per thread, so a ramp from zero gets going
+    Duration::from_secs_f64(1.0 / (rate / args.threads as f64).max(1.0))
+}
+
+// think time of the next request, jittered pauses never drop below zero
+fn sample_think_time(rng: &mut impl Rng, think_time_us: u64, jitter_us: u64) -> Duration {
+    if jitter_us == 0 {
+        return Duration::from_micros(think_time_us);
+    }
+    let low = think_time_us.saturating_sub(jitter_us);
+    Duration::from_micros(rng.gen_range(low..=think_time_us.saturating_add(jitter_us)))
+}
+
+// raw bytes stored with --set-flags, the memcache crate's own values bring fixed flags
+struct FlaggedValue<'a> {
+    value: &'a [u8],
+    flags: u32,
+}
+
+impl<W: std::io::Write> memcache::ToMemcacheValue<W> for FlaggedValue<'_> {
+    fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    fn get_length(&self) -> usize {
+        self.value.len()
+    }
+
+    fn write_to(&self, stream: &mut W) -> std::io::Result<()> {
+        stream.write_all(self.value)
+    }
 }
 
 fn set_memcached_value(
     server: &memcache::Client,
-    test_dict: Arc<HashMap<String, String>>,
+    test_dict: Arc<HashMap<String, Vec<u8>>>,
+    rng: &mut impl Rng,
+    ttl: u32,
+    ttl_jitter: u32,
+    flags: u32,
 ) -> std::result::Result<(), MemcacheError> {
     server.flush()?;
+    store_memcached_values(server, &test_dict, rng, ttl, ttl_jitter, flags)
+}
 
+// stores the dataset values over whatever the keys hold, without flushing first
+fn store_memcached_values(
+    server: &memcache::Client,
+    test_dict: &HashMap<String, Vec<u8>>,
+    rng: &mut impl Rng,
+    ttl: u32,
+    ttl_jitter: u32,
+    flags: u32,
+) -> std::result::Result<(), MemcacheError> {
     // set a string value:
     for (key, value) in test_dict.iter() {
-        server.set(key.as_str(), value, 0)?;
+        let value = FlaggedValue {
+            value: value.as_slice(),
+            flags,
+        };
+        server.set(key.as_str(), value, sample_ttl(rng, ttl, ttl_jitter))?;
     }
 
     Ok(())
 }
 
+fn set_counter_values(
+    server: &memcache::Client,
+    test_dict: Arc<HashMap<String, Vec<u8>>>,
+) -> std::result::Result<(), MemcacheError> {
+    // incr/decr only work on numeric values, so overwrite every key with a counter
+    for key in test_dict.keys() {
+        server.set(key.as_str(), COUNTER_INITIAL_VALUE, 0)?;
+    }
+
+    Ok(())
+}
+
+// pre-flight check: fails fast when the server does not answer, returns its version
+fn health_check(server: &memcache::Client) -> std::result::Result<String, MemcacheError> {
+    let versions = server.version()?;
+    Ok(versions
+        .into_iter()
+        .next()
+        .map(|(_, version)| version)
+        .unwrap_or_default())
+}
+
+// major.minor.patch of a version string such as 1.6.21, any suffix after the numbers is ignored
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim().split('.').map(|part| {
+        let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        part[..digits].parse::<u32>().ok()
+    });
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+// fails when the server `version` is older than the binary protocol get_server's client
+// speaks, or than a release that knows every one of `required_ops`. a version that does not
+// parse, e.g. of a proxy, skips the check
+fn check_server_compatibility(
+    version: &str,
+    required_ops: &[Operation],
+    protocol: Protocol,
+) -> Result<(), Box<dyn Error>> {
+    let Some(parsed) = parse_version(version) else {
+        tracing::warn!(version = %version, "unknown server version, not checking what it supports");
+        return Ok(());
+    };
+    let format = |(major, minor, patch): (u32, u32, u32)| format!("{}.{}.{}", major, minor, patch);
+    if parsed < BINARY_PROTOCOL_MIN_VERSION {
+        return Err(format!(
+            "memcached {} does not speak the binary protocol the dataset is stored over, it needs {} or later",
+            version,
+            format(BINARY_PROTOCOL_MIN_VERSION)
+        )
+        .into());
+    }
+    for &(operation, min_version) in OPERATION_MIN_VERSIONS.iter() {
+        if required_ops.contains(&operation) && parsed < min_version {
+            return Err(format!(
+                "memcached {} does not support {}, it needs {} or later",
+                version,
+                operation.name(),
+                format(min_version)
+            )
+            .into());
+        }
+    }
+    if protocol == Protocol::Udp && parsed < UDP_MIN_VERSION {
+        tracing::warn!(
+            version = %version,
+            "the udp support of memcached before {} is unreliable, consider -l tcp",
+            format(UDP_MIN_VERSION)
+        );
+    }
+    Ok(())
+}
+
+fn take_stats_snapshot(
+    server: &memcache::Client,
+) -> std::result::Result<HashMap<String, String>, MemcacheError> {
+    Ok(server
+        .stats()?
+        .into_iter()
+        .next()
+        .map(|(_, stats)| stats)
+        .unwrap_or_default())
+}
+
+// a snapshot of every server, on the blocking pool like the rest of the synchronous client
+async fn take_stats_snapshots(
+    servers: &[memcache::Client],
+) -> Result<Vec<HashMap<String, String>>, Box<dyn Error>> {
+    let servers = servers.to_vec();
+    Ok(tokio::task::spawn_blocking(move || {
+        servers
+            .iter()
+            .map(take_stats_snapshot)
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await??)
+}
+
+// change of every one of `fields`, a field missing from either snapshot is skipped
+fn stats_deltas(
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+    fields: &[&'static str],
+) -> Vec<(&'static str, i64)> {
+    let value = |stats: &HashMap<String, String>, field: &str| {
+        stats.get(field).and_then(|v| v.parse::<i64>().ok())
+    };
+    fields
+        .iter()
+        .filter_map(|&field| Some((field, value(after, field)? - value(before, field)?)))
+        .collect()
+}
+
+// one of the STATS_DELTA_FIELDS, 0 when either snapshot lacks it
+fn field_delta(
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+    field: &str,
+) -> i64 {
+    stats_deltas(before, after, &STATS_DELTA_FIELDS)
+        .into_iter()
+        .find(|(name, _)| *name == field)
+        .map_or(0, |(_, delta)| delta)
+}
+
+// --measure-evictions: the evictions field of the deltas over the run
+fn eviction_summary(
+    addr: &str,
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+    duration: Duration,
+) -> EvictionSummary {
+    let (evictions, sets) = (
+        field_delta(before, after, "evictions"),
+        field_delta(before, after, "cmd_set"),
+    );
+    EvictionSummary {
+        server: addr.to_string(),
+        evictions,
+        evictions_per_sec: evictions as f64 / duration.as_secs_f64(),
+        evictions_per_set: match sets {
+            0 => 0.0,
+            sets => evictions as f64 / sets as f64,
+        },
+    }
+}
+
+// the bytes stat over the dataset and the benchmark, 0 when either snapshot lacks it
+fn memory_delta(
+    addr: &str,
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+) -> MemoryDelta {
+    let bytes = stats_deltas(before, after, &["bytes"])
+        .first()
+        .map_or(0, |&(_, delta)| delta);
+    MemoryDelta {
+        server: addr.to_string(),
+        memory_added_bytes: bytes,
+        memory_added_mb: bytes as f64 / 1e6,
+    }
+}
+
+// the deltas as `field +delta`
+fn stats_delta(
+    before: &HashMap<String, String>,
+    after: &HashMap<String, String>,
+    fields: &[&'static str],
+) -> String {
+    stats_deltas(before, after, fields)
+        .iter()
+        .map(|(field, delta)| format!("{} {:+}", field, delta))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// --stats-interval-s: logs the change of the STATS_POLL_FIELDS of every server since the
+// previous poll until aborted. a server whose stats fail is tried again on the next tick
+// --tcp-keepalive-s for the memcache clients: a version request every `interval` stands in
+// for the keep-alive probe, so a client left idle through the passes keeps its connection
+async fn keepalive_pinger(servers: Vec<(String, memcache::Client)>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // the first tick completes immediately, the clients were just used
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        for (addr, server) in servers.iter() {
+            let server = server.clone();
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(move || server.version()).await {
+                tracing::warn!(server = %addr, error = %e, "keep-alive request failed");
+            }
+        }
+    }
+}
+
+async fn stats_poller(servers: Vec<(String, memcache::Client)>, interval: Duration) {
+    let mut previous: Vec<Option<HashMap<String, String>>> = vec![None; servers.len()];
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        // the first tick completes immediately and takes the baseline
+        ticker.tick().await;
+        for ((addr, server), previous) in servers.iter().zip(previous.iter_mut()) {
+            let server = server.clone();
+            let snapshot =
+                match tokio::task::spawn_blocking(move || take_stats_snapshot(&server)).await {
+                    Ok(Ok(snapshot)) => snapshot,
+                    Ok(Err(e)) => {
+                        tracing::warn!(server = %addr, error = %e, "failed to poll server stats");
+                        continue;
+                    }
+                    Err(_) => continue,
+                };
+            if let Some(before) = previous {
+                tracing::info!(
+                    server = %addr,
+                    deltas = %stats_delta(before, &snapshot, &STATS_POLL_FIELDS),
+                    "server stats"
+                );
+            }
+            *previous = Some(snapshot);
+        }
+    }
+}
+
 fn exmaple_method(server: &memcache::Client) -> std::result::Result<(), MemcacheError> {
     // flush the database:
     server.flush()?;
@@ -114,13 +1071,19 @@ fn exmaple_method(server: &memcache::Client) -> std::result::Result<(), Memcache
     let answer: i32 = server.get("counter")?.unwrap();
     assert_eq!(answer, 42);
 
-    println!("memcached server works!");
+    tracing::info!("memcached server works!");
     Ok(())
 }
 
-async fn wrap_get_command(key: String, seq: u16) -> Vec<u8> {
+// bytes of a single-key get reply: "VALUE <key> <flags> <bytes>\r\n<data>\r\nEND\r\n"
+fn get_reply_len(key_size: usize, value_size: usize) -> usize {
+    format!("VALUE  0 {}\r\n", value_size).len() + key_size + value_size + "\r\nEND\r\n".len()
+}
+
+// every UDP request is prefixed with an 8-byte frame header:
+// request id (2 bytes), sequence number (2), total datagrams (2), reserved (2)
+fn wrap_udp_frame(seq: u16, mut command: Vec<u8>) -> Vec<u8> {
     let mut bytes: Vec<u8> = vec![0, 0, 0, 1, 0, 0];
-    let mut command = format!("get {}\r\n", key).into_bytes();
     let mut seq_bytes = seq.to_be_bytes().to_vec();
     seq_bytes.append(&mut bytes);
     seq_bytes.append(&mut command);
@@ -128,161 +1091,3154 @@ async fn wrap_get_command(key: String, seq: u16) -> Vec<u8> {
     seq_bytes
 }
 
-struct TaskData {
-    buf: Vec<u8>,
-    addr: String,
-    key: String,
-    test_dict: Arc<HashMap<String, String>>,
-    validate: bool,
-    key_size: usize,
-    value_size: usize,
+fn wrap_get_command(key: &str, seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("get {}\r\n", key).into_bytes())
 }
 
-async fn socket_task(socket: Arc<UdpSocket>, mut rx: mpsc::Receiver<TaskData>) {
-    while let Some(TaskData {
-        buf,
-        addr,
-        key,
-        test_dict,
-        validate,
-        key_size,
-        value_size,
-    }) = rx.recv().await
-    {
-        // Send
-        let _ = socket.send_to(&buf[..], &addr).await;
-
-        // Then receive
-        let mut buf = [0; BUFFER_SIZE];
-        let my_duration = tokio::time::Duration::from_millis(500);
-
-        // timeout(my_duration, socket.recv_from(&mut buf)).await
-        if let Ok(Ok((amt, _))) = timeout(my_duration, socket.recv_from(&mut buf)).await {
-            if validate {
-                if let Some(value) = test_dict.get(&key) {
-                    let received = String::from_utf8_lossy(&buf[..amt])
-                        .split("VALUE ")
-                        .nth(1)
-                        .unwrap_or_default()[6 + key_size + 1..6 + key_size + value_size + 1]
-                        .to_string();
-
-                    if received != *value.to_string() {
-                        println!(
-                            "response not match key {} buf: {} , value: {}",
-                            key, received, value
-                        );
-                    }
-                }
-            }
-        }
-    }
+fn wrap_multi_get_command(keys: &[&str], seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("get {}\r\n", keys.join(" ")).into_bytes())
 }
 
-// TODO add mutiple thread support
-async fn get_command_benchmark(
-    test_dict: Arc<HashMap<String, String>>,
-    nums: usize,
-) -> Result<(), Box<dyn Error>> {
-    let args = Cli::parse();
-    let keys: Vec<&String> = test_dict.keys().collect();
-
-    // assign client address
-    let addr = format!("{}:{}", args.server_address, args.port);
-    let socket = UdpSocket::bind("0.0.0.0:0").await?;
-    let socket = Arc::new(socket);
-
-    let start = std::time::Instant::now();
-    let dict_len = keys.len();
-
-    let mut seq: u16 = 0;
-
-    // Create the channel
-    let (tx, rx) = mpsc::channel(100000);
-    let socket_clone = Arc::clone(&socket);
-    let socket_task = tokio::spawn(socket_task(socket_clone, rx));
+// "<command line>\r\n<data block>\r\n", the data block is sent as raw bytes
+fn storage_command(line: String, value: &[u8]) -> Vec<u8> {
+    let mut command = line.into_bytes();
+    command.extend_from_slice(b"\r\n");
+    command.extend_from_slice(value);
+    command.extend_from_slice(b"\r\n");
+    command
+}
 
-    for _ in 0..nums {
-        let rng = rand::thread_rng().gen_range(0..dict_len - 1);
-        let key = keys[rng].clone();
-        // let addr_clone = Arc::clone(&addr);
-        let packet = wrap_get_command(key.clone(), seq).await;
-        seq = seq.wrapping_add(1);
+fn wrap_set_command(key: &str, value: &[u8], flags: u32, ttl: u32, seq: u16) -> Vec<u8> {
+    let line = format!("set {} {} {} {}", key, flags, ttl, value.len());
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-        let send_result = tx
-            .send(TaskData {
-                buf: packet,
-                addr: addr.clone(),
-                key,
-                test_dict: test_dict.clone(),
-                validate: args.validate,
-                key_size: args.key_size,
-                value_size: args.value_size,
-            })
-            .await;
-        if send_result.is_err() {
-            // The receiver was dropped, break the loop
-            break;
-        }
-    }
+// like set, but the server answers NOT_STORED unless the key already exists
+fn wrap_replace_command(key: &str, value: &[u8], flags: u32, ttl: u32, seq: u16) -> Vec<u8> {
+    let line = format!("replace {} {} {} {}", key, flags, ttl, value.len());
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-    // Close the channel
-    drop(tx);
+// like set, but the server answers NOT_STORED when the key already exists
+fn wrap_add_command(key: &str, value: &[u8], flags: u32, ttl: u32, seq: u16) -> Vec<u8> {
+    let line = format!("add {} {} {} {}", key, flags, ttl, value.len());
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-    // Wait for the socket task to finish
-    socket_task.await?;
+fn wrap_prepend_command(key: &str, value: &[u8], seq: u16) -> Vec<u8> {
+    let line = format!("prepend {} 0 0 {}", key, value.len());
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-    let duration = start.elapsed();
-    println!("Time elapsed in get_command_benchmark() is: {:?}", duration);
+fn wrap_append_command(key: &str, value: &[u8], seq: u16) -> Vec<u8> {
+    let line = format!("append {} 0 0 {}", key, value.len());
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-    Ok(())
+fn wrap_delete_command(key: &str, seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("delete {}\r\n", key).into_bytes())
 }
 
-fn get_server(
-    addr: &String,
-    port: &String,
-    protocol: &Protocol,
-) -> Result<memcache::Client, MemcacheError> {
-    match protocol {
-        Protocol::Udp => memcache::connect(format!("memcache+udp://{}:{}?timeout=10", addr, port)),
-        Protocol::Tcp => memcache::connect(format!("memcache://{}:{}?timeout=10", addr, port)),
-    }
+fn wrap_incr_command(key: &str, delta: u64, seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("incr {} {}\r\n", key, delta).into_bytes())
 }
 
-#[tokio::main]
-async fn main() -> std::result::Result<(), Box<dyn Error>> {
-    let args = Cli::parse();
+fn wrap_decr_command(key: &str, delta: u64, seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("decr {} {}\r\n", key, delta).into_bytes())
+}
 
-    let server = get_server(&args.server_address, &args.port, &args.protocol)?;
-    exmaple_method(&server)?;
+fn wrap_gets_command(key: &str, seq: u16) -> Vec<u8> {
+    wrap_udp_frame(seq, format!("gets {}\r\n", key).into_bytes())
+}
 
-    let test_dict = generate_memcached_test_dict(args.key_size, args.value_size, NUM_ENTRIES);
+fn wrap_cas_command(key: &str, value: &[u8], flags: u32, cas_token: u64, seq: u16) -> Vec<u8> {
+    let line = format!("cas {} {} 0 {} {}", key, flags, value.len(), cas_token);
+    wrap_udp_frame(seq, storage_command(line, value))
+}
 
-    let test_dict = Arc::new(test_dict);
+// (key, data) of every VALUE block in a retrieval response
+fn parse_value_blocks(buf: &[u8]) -> Vec<(String, &[u8])> {
+    parse_blocks(buf)
+        .blocks
+        .into_iter()
+        .map(|block| (block.key, &buf[block.data]))
+        .collect()
+}
 
-    // assign test_dict to server
-    set_memcached_value(&server, test_dict.clone())?;
+// VALUE <key> <flags> <bytes> <cas unique>\r\n
+fn parse_cas_token(buf: &[u8]) -> Option<u64> {
+    parse_blocks(buf).blocks.first()?.cas
+}
 
-    let mut handles = vec![];
+impl Operation {
+    fn name(&self) -> String {
+        format!("{:?}", self).to_lowercase()
+    }
 
-    for _ in 0..args.threads {
-        let test_dict = Arc::clone(&test_dict);
-        let handle = tokio::spawn(async move {
-            match get_command_benchmark(test_dict, args.nums).await {
-                Ok(_) => (),
-                Err(e) => eprintln!("Task failed with error: {:?}", e),
-            }
-        });
-        handles.push(handle);
+    fn wrap_command(&self, key: &str, value: &[u8], flags: u32, ttl: u32, seq: u16) -> Vec<u8> {
+        match self {
+            Operation::Get => wrap_get_command(key, seq),
+            Operation::Set => wrap_set_command(key, value, flags, ttl, seq),
+            Operation::Delete => wrap_delete_command(key, seq),
+            Operation::Incr => wrap_incr_command(key, COUNTER_DELTA, seq),
+            Operation::Decr => wrap_decr_command(key, COUNTER_DELTA, seq),
+            Operation::Replace => wrap_replace_command(key, value, flags, ttl, seq),
+            Operation::Add => wrap_add_command(key, value, flags, ttl, seq),
+            Operation::Prepend => wrap_prepend_command(key, PREPEND_DATA.as_bytes(), seq),
+            Operation::Append => wrap_append_command(key, APPEND_DATA.as_bytes(), seq),
+            // the cas command itself is sent once the gets reply carries the token
+            Operation::Gets | Operation::Cas => wrap_gets_command(key, seq),
+            Operation::MultiGet => wrap_multi_get_command(&[key], seq),
+        }
     }
 
-    // wait for all tasks to complete
-    for handle in handles {
-        handle.await?;
+    // length of the first complete response in `buf`, None while more bytes are needed.
+    // retrieval responses are VALUE blocks terminated by END, everything else is one line
+    fn response_len(&self, buf: &[u8]) -> Option<usize> {
+        let line_end = |from: usize| {
+            buf[from..]
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .map(|i| from + i + 2)
+        };
+
+        match self {
+            Operation::Get | Operation::Gets | Operation::MultiGet => parse_blocks(buf).len,
+            Operation::Set
+            | Operation::Delete
+            | Operation::Incr
+            | Operation::Decr
+            | Operation::Cas
+            | Operation::Prepend
+            | Operation::Append
+            | Operation::Replace
+            | Operation::Add => line_end(0),
+        }
     }
+}
 
-    // stats
-    let stats = server.stats()?;
-    println!("stats: {:?}", stats);
+enum Connection {
+    // --udp-sockets sockets, the index of the one the last request went out on and the
+    // request id from the frame header of the last reply read
+    Udp(Vec<UdpSocket>, usize, u16),
+    // bytes read past the end of the last response, pipelined replies arrive back to back
+    Tcp(TcpStream, Vec<u8>),
+    Unix(UnixStream, Vec<u8>),
+    Tls(Box<TlsStream<TcpStream>>, Vec<u8>),
+}
 
+// moves the first complete response of a stream connection into `buf`. partial reads
+// stay in `pending`, so a timed out recv loses nothing
+async fn read_response(
+    stream: &mut (impl AsyncRead + Unpin),
+    pending: &mut Vec<u8>,
+    operation: Operation,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let mut chunk = [0; BUFFER_SIZE];
+    let len = loop {
+        if let Some(len) = operation.response_len(pending) {
+            break len;
+        }
+        let amt = stream.read(&mut chunk).await?;
+        if amt == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        pending.extend_from_slice(&chunk[..amt]);
+    };
+    buf.extend(pending.drain(..len));
     Ok(())
 }
+
+// the ephemeral UDP socket of a connection, with the buffer sizes set before it is bound
+fn bind_udp_socket(args: &Cli, ipv6: bool) -> std::io::Result<Socket> {
+    let (domain, local) = match ipv6 {
+        true => (Domain::IPV6, SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))),
+        false => (Domain::IPV4, SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))),
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    if let Some(bytes) = args.udp_send_buffer_bytes {
+        socket.set_send_buffer_size(bytes)?;
+    }
+    if let Some(bytes) = args.udp_recv_buffer_bytes {
+        socket.set_recv_buffer_size(bytes)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&local.into())?;
+    Ok(socket)
+}
+
+// --tcp-keepalive-s: SO_KEEPALIVE with TCP_KEEPIDLE set to `idle_s`
+fn set_tcp_keepalive(stream: &TcpStream, idle_s: Option<u64>) -> std::io::Result<()> {
+    match idle_s {
+        Some(secs) => SockRef::from(stream)
+            .set_tcp_keepalive(&TcpKeepalive::new().with_time(Duration::from_secs(secs))),
+        None => Ok(()),
+    }
+}
+
+impl Connection {
+    // `tls` is the client config of -l tls
+    async fn connect(
+        args: &Cli,
+        addr: &str,
+        tls: Option<&Arc<ClientConfig>>,
+    ) -> std::io::Result<Self> {
+        match args.protocol {
+            Protocol::Udp => {
+                let ipv6 = addr.parse::<SocketAddr>().is_ok_and(|addr| addr.is_ipv6());
+                let mut sockets = vec![];
+                for _ in 0..args.udp_sockets {
+                    sockets.push(UdpSocket::from_std(bind_udp_socket(args, ipv6)?.into())?);
+                }
+                Ok(Connection::Udp(sockets, 0, 0))
+            }
+            Protocol::Tcp => {
+                let stream = TcpStream::connect(addr).await?;
+                set_tcp_keepalive(&stream, args.tcp_keepalive_s)?;
+                Ok(Connection::Tcp(stream, vec![]))
+            }
+            // `addr` is the socket path
+            Protocol::Unix => Ok(Connection::Unix(UnixStream::connect(addr).await?, vec![])),
+            Protocol::Tls => {
+                let config = tls.ok_or(std::io::ErrorKind::InvalidInput)?;
+                let stream = tls::connect(config, addr).await?;
+                set_tcp_keepalive(stream.get_ref().0, args.tcp_keepalive_s)?;
+                Ok(Connection::Tls(Box::new(stream), vec![]))
+            }
+        }
+    }
+
+    // plain ASCII commands, back to back, over a stream connection
+    async fn write_commands(&mut self, commands: &[u8]) -> std::io::Result<()> {
+        match self {
+            Connection::Udp(..) => Err(std::io::ErrorKind::Unsupported.into()),
+            Connection::Tcp(stream, _) => stream.write_all(commands).await,
+            Connection::Unix(stream, _) => stream.write_all(commands).await,
+            Connection::Tls(stream, _) => stream.write_all(commands).await,
+        }
+    }
+
+    async fn send(&mut self, packet: &[u8], addr: &str) -> std::io::Result<()> {
+        match self {
+            // the reply is read from the socket the request went out on
+            Connection::Udp(sockets, current, _) => {
+                *current = (*current + 1) % sockets.len();
+                sockets[*current].send_to(packet, addr).await.map(|_| ())
+            }
+            // streams carry the plain ASCII command without the UDP frame header
+            Connection::Tcp(..) | Connection::Unix(..) | Connection::Tls(..) => {
+                self.write_commands(&packet[UDP_HEADER_SIZE..]).await
+            }
+        }
+    }
+
+    async fn recv(&mut self, operation: Operation, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        buf.clear();
+        match self {
+            Connection::Udp(sockets, current, request_id) => {
+                let socket = &sockets[*current];
+                buf.resize(BUFFER_SIZE, 0);
+                let (amt, _) = socket.recv_from(buf).await?;
+                buf.truncate(amt);
+                match frame_header(buf) {
+                    // a reply too large for one datagram comes as several of the same request
+                    // id, a lost one leaves the reply to the receive timeout
+                    Some((id, _, total)) if total > 1 => {
+                        let mut datagrams = vec![std::mem::take(buf)];
+                        while datagrams.len() < total as usize {
+                            let mut datagram = vec![0; BUFFER_SIZE];
+                            let (amt, _) = socket.recv_from(&mut datagram).await?;
+                            datagram.truncate(amt);
+                            if frame_header(&datagram).is_some_and(|(other, _, _)| other == id) {
+                                datagrams.push(datagram);
+                            }
+                        }
+                        let bufs: Vec<&[u8]> = datagrams.iter().map(Vec::as_slice).collect();
+                        let parsed = parse_udp_response(&bufs);
+                        if !parsed.complete {
+                            tracing::debug!(
+                                request_id = parsed.request_id,
+                                missing_datagrams = parsed.missing_datagrams,
+                                "incomplete udp reply"
+                            );
+                        }
+                        *request_id = parsed.request_id;
+                        *buf = parsed.payload;
+                    }
+                    _ => {
+                        if amt >= 2 {
+                            *request_id = u16::from_be_bytes([buf[0], buf[1]]);
+                        }
+                        // hand back only the ASCII reply so both protocols parse the same bytes
+                        buf.drain(..UDP_HEADER_SIZE.min(amt));
+                    }
+                }
+            }
+            Connection::Tcp(stream, pending) => {
+                read_response(stream, pending, operation, buf).await?
+            }
+            Connection::Unix(stream, pending) => {
+                read_response(stream, pending, operation, buf).await?
+            }
+            Connection::Tls(stream, pending) => {
+                read_response(stream.as_mut(), pending, operation, buf).await?
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+// --validate-sequence: how UDP replies lined up with the request in flight
+#[derive(Default)]
+struct SequenceStats {
+    // requests that timed out without their reply
+    dropped: usize,
+    // replies to earlier requests that arrived after those timed out, skipped
+    late: usize,
+    // replies carrying a request id that was not sent yet
+    out_of_order: usize,
+}
+
+impl SequenceStats {
+    fn merge(&mut self, other: &SequenceStats) {
+        self.dropped += other.dropped;
+        self.late += other.late;
+        self.out_of_order += other.out_of_order;
+    }
+
+    fn print(&self, out: &mut impl Write, label: &str) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{} sequence: {} dropped, {} late, {} out of order",
+            label, self.dropped, self.late, self.out_of_order
+        )
+    }
+}
+
+// reads the reply to request `seq`. with a sequence tracker, UDP replies to earlier
+// requests are counted and skipped so they are not taken for the reply in flight
+async fn recv_reply(
+    connection: &mut Connection,
+    operation: Operation,
+    buf: &mut Vec<u8>,
+    seq: u16,
+    recv_timeout: Duration,
+    mut sequence: Option<&mut SequenceStats>,
+) -> Result<std::io::Result<usize>, Elapsed> {
+    let deadline = tokio::time::Instant::now() + recv_timeout;
+    loop {
+        let received = timeout_at(deadline, connection.recv(operation, buf)).await;
+        let (Some(sequence), Connection::Udp(_, _, request_id)) =
+            (sequence.as_deref_mut(), &connection)
+        else {
+            return received;
+        };
+        match received {
+            Ok(Ok(_)) if *request_id == seq => return received,
+            // ids wrap around, anything less than half the id space behind is an older request
+            Ok(Ok(_)) if seq.wrapping_sub(*request_id) < 0x8000 => sequence.late += 1,
+            Ok(Ok(_)) => {
+                sequence.out_of_order += 1;
+                return received;
+            }
+            Err(_) => {
+                sequence.dropped += 1;
+                return received;
+            }
+            Ok(Err(_)) => return received,
+        }
+    }
+}
+
+// cas tokens seen by a gets pass, shared by every thread of the run. a cas request takes
+// the token of its key, if any, and skips its own gets round trip
+#[derive(Default, Clone)]
+struct CasTokenStore {
+    // by server and key, every server has tokens of its own
+    tokens: Arc<Mutex<HashMap<(String, String), u64>>>,
+}
+
+impl CasTokenStore {
+    fn insert(&self, addr: &str, key: &str, token: u64) {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert((addr.to_string(), key.to_string()), token);
+    }
+
+    // a token is only good for one cas, the store changes it
+    fn take(&self, addr: &str, key: &str) -> Option<u64> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .remove(&(addr.to_string(), key.to_string()))
+    }
+}
+
+struct TaskData {
+    buf: Vec<u8>,
+    // the server of the request, `addr` is the one at index `server` in the pass addrs
+    server: usize,
+    addr: String,
+    key: String,
+    // every key of a multi-get, `key` is the first of them
+    batch: Vec<String>,
+    seq: u16,
+    operation: Operation,
+    test_dict: Arc<HashMap<String, Vec<u8>>>,
+    validate: bool,
+    integrity_hash: Option<IntegrityHash>,
+    // a cas sent with a stored token from CasTokenStore instead of its own gets first
+    cas_token: Option<u64>,
+}
+
+#[derive(Default)]
+struct TaskStats {
+    latencies: LatencyRecorder,
+    // requests that got no reply: send/receive errors and timeouts
+    errors: usize,
+    // sends that failed and were tried again
+    retries: usize,
+    // requests whose send still failed after --max-retries, also counted in errors
+    permanent_errors: usize,
+    // wall-clock time of the timed requests, the slowest thread once merged
+    duration: Duration,
+    // response status (first word of the reply, e.g. STORED, DELETED, NOT_FOUND) -> count
+    responses: HashMap<String, usize>,
+    sequence: SequenceStats,
+    packet_loss: PacketLossTracker,
+    // most requests seen queued in the channel at once, the highest thread once merged
+    peak_channel_occupancy: usize,
+}
+
+impl TaskStats {
+    fn merge(&mut self, other: &TaskStats) {
+        self.latencies.merge(&other.latencies);
+        self.errors += other.errors;
+        self.retries += other.retries;
+        self.permanent_errors += other.permanent_errors;
+        self.duration = self.duration.max(other.duration);
+        self.sequence.merge(&other.sequence);
+        self.packet_loss.merge(&other.packet_loss);
+        self.peak_channel_occupancy = self
+            .peak_channel_occupancy
+            .max(other.peak_channel_occupancy);
+        for (status, count) in other.responses.iter() {
+            *self.responses.entry(status.clone()).or_default() += count;
+        }
+    }
+
+    fn summary(&self, duration: Duration) -> Summary {
+        Summary {
+            retry_count: self.retries,
+            permanent_error_count: self.permanent_errors,
+            timeout_count: self.packet_loss.timeouts(),
+            socket_error_count: self.packet_loss.socket_errors(),
+            packet_loss_rate: self.packet_loss.loss_rate(self.completed()),
+            peak_channel_occupancy: self.peak_channel_occupancy,
+            ..Summary::new(
+                duration,
+                self.latencies.len(),
+                self.errors,
+                &self.latencies.percentiles(),
+            )
+        }
+    }
+
+    // requests answered or failed
+    fn completed(&self) -> u64 {
+        (self.latencies.len() + self.errors) as u64
+    }
+
+    // fraction of the requests issued that failed
+    fn error_rate(&self) -> f64 {
+        match self.completed() {
+            0 => 0.0,
+            completed => self.errors as f64 / completed as f64,
+        }
+    }
+
+    // a failed request also counts towards the run-wide counters
+    fn record_error(&mut self, shared: &BenchmarkStats, kind: ErrorKind) {
+        self.errors += 1;
+        shared.record_error(kind);
+    }
+
+    // the progress bar moves in steps of PROGRESS_INTERVAL requests
+    fn report_progress(&self, progress: &ProgressBar) {
+        if self.completed().is_multiple_of(PROGRESS_INTERVAL) {
+            progress.inc(PROGRESS_INTERVAL);
+        }
+    }
+
+    fn print_responses(&self, out: &mut impl Write, label: &str) -> std::io::Result<()> {
+        let mut responses: Vec<_> = self.responses.iter().collect();
+        responses.sort();
+        writeln!(out, "{} responses: {:?}", label, responses)
+    }
+}
+
+fn response_status(buf: &[u8]) -> String {
+    String::from_utf8_lossy(buf)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn record_response(
+    task: &TaskData,
+    response: &[u8],
+    stats: &mut TaskStats,
+    shared: &BenchmarkStats,
+    hits: &HitCounter,
+) {
+    *stats
+        .responses
+        .entry(response_status(response))
+        .or_default() += 1;
+
+    // the gets sent for a cas is bookkeeping, only reads of the benchmark count towards the hit rate
+    let requested = match task.operation {
+        Operation::Get | Operation::Gets => 1,
+        Operation::MultiGet => task.batch.len(),
+        _ => 0,
+    };
+    if requested > 0 {
+        let found = parse_value_blocks(response).len().min(requested);
+        hits.record(found as u64, (requested - found) as u64);
+    }
+
+    if !task.validate {
+        return;
+    }
+
+    let key = &task.key;
+    match task.operation {
+        Operation::Get | Operation::Gets => {
+            if let Some(value) = task.test_dict.get(key) {
+                // a miss (e.g. after a delete pass) has no VALUE block and reads as empty
+                let blocks = parse_value_blocks(response);
+                let received = blocks.first().map(|(_, data)| *data).unwrap_or_default();
+
+                if received != value.as_slice() {
+                    shared.record_validation_mismatch();
+                    tracing::warn!(
+                        key = %key,
+                        buf = %String::from_utf8_lossy(received),
+                        value = %String::from_utf8_lossy(value),
+                        "response not match"
+                    );
+                }
+                if let (Some(hash), false) = (task.integrity_hash, blocks.is_empty()) {
+                    if !hash.verify(received) {
+                        shared.record_validation_mismatch();
+                        tracing::warn!(
+                            key = %key,
+                            buf = %String::from_utf8_lossy(received),
+                            "integrity hash mismatch"
+                        );
+                    }
+                }
+            }
+        }
+        Operation::Set => {
+            if response_status(response) != "STORED" {
+                tracing::warn!(
+                    key = %key,
+                    buf = %String::from_utf8_lossy(response),
+                    "set not stored"
+                );
+            }
+        }
+        // NOT_STORED means the key was gone, e.g. after a delete pass
+        Operation::Prepend | Operation::Append | Operation::Replace => {
+            let status = response_status(response);
+            if status != "STORED" && status != "NOT_STORED" {
+                tracing::warn!(
+                    operation = %task.operation.name(),
+                    key = %key,
+                    buf = %status,
+                    "unexpected response"
+                );
+            }
+        }
+        // every dataset key was stored up front, so only keys outside it can be added
+        Operation::Add => {
+            let status = response_status(response);
+            let expected = match task.test_dict.contains_key(key) {
+                true => "NOT_STORED",
+                false => "STORED",
+            };
+            if status != expected {
+                tracing::warn!(expected, key = %key, buf = %status, "unexpected add response");
+            }
+        }
+        Operation::Delete => {
+            let status = response_status(response);
+            if status != "DELETED" && status != "NOT_FOUND" {
+                tracing::warn!(key = %key, buf = %status, "unexpected delete response");
+            }
+        }
+        Operation::Incr | Operation::Decr => {
+            let status = response_status(response);
+            if status.parse::<u64>().is_err() {
+                tracing::warn!(key = %key, buf = %status, "counter not updated");
+            }
+        }
+        Operation::Cas => {
+            let status = response_status(response);
+            if !matches!(status.as_str(), "STORED" | "EXISTS" | "NOT_FOUND" | "END") {
+                tracing::warn!(key = %key, buf = %status, "unexpected cas response");
+            }
+        }
+        Operation::MultiGet => {
+            let blocks = parse_value_blocks(response);
+            for (key, data) in blocks.iter() {
+                if let Some(value) = task.test_dict.get(key) {
+                    if data != value {
+                        shared.record_validation_mismatch();
+                        tracing::warn!(
+                            key = %key,
+                            buf = %String::from_utf8_lossy(data),
+                            value = %String::from_utf8_lossy(value),
+                            "response not match"
+                        );
+                    }
+                }
+            }
+            // repeated keys in one request are answered once per occurrence
+            if blocks.len() != task.batch.len() {
+                tracing::warn!(
+                    returned = blocks.len(),
+                    requested = task.batch.len(),
+                    keys = ?task.batch,
+                    "multi-get returned too few values"
+                );
+            }
+        }
+    }
+}
+
+// requests still queued when `deadline` passes are dropped unsent
+fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+// sends `packet`, retrying up to `max_retries` times after a short pause.
+// returns false once every attempt has failed
+async fn send_with_retries(
+    connection: &mut Connection,
+    packet: &[u8],
+    addr: &str,
+    max_retries: u32,
+    stats: &mut TaskStats,
+    bytes: &ByteCounter,
+) -> bool {
+    for attempt in 0..=max_retries {
+        if connection.send(packet, addr).await.is_ok() {
+            bytes.record_sent(packet.len() - UDP_HEADER_SIZE);
+            return true;
+        }
+        if attempt < max_retries {
+            stats.retries += 1;
+            tokio::time::sleep(RETRY_BACKOFF).await;
+        }
+    }
+    stats.permanent_errors += 1;
+    false
+}
+
+// a reply that is empty or an ERROR/CLIENT_ERROR/SERVER_ERROR line instead of a response
+fn is_error_reply(buf: &[u8]) -> bool {
+    matches!(
+        response_status(buf).as_str(),
+        "" | "ERROR" | "CLIENT_ERROR" | "SERVER_ERROR"
+    )
+}
+
+// an error reply without any status word is one the benchmark could not make sense of
+fn error_reply_kind(buf: &[u8]) -> ErrorKind {
+    match response_status(buf).as_str() {
+        "" => ErrorKind::ParseError,
+        _ => ErrorKind::ErrorReply,
+    }
+}
+
+async fn socket_task(
+    mut connections: Vec<Connection>,
+    mut rx: mpsc::Receiver<TaskData>,
+    pass: PassContext,
+    deadline: Option<Instant>,
+) -> std::io::Result<(Vec<Connection>, TaskStats)> {
+    let mut stats = TaskStats::default();
+    let mut buf = Vec::with_capacity(BUFFER_SIZE);
+
+    while let Some(task) = rx.recv().await {
+        if deadline_passed(deadline) || pass.cancel.is_cancelled() {
+            break;
+        }
+        let server = task.server;
+        send_request(&mut connections[server], task, &pass, &mut stats, &mut buf).await;
+    }
+
+    pass.progress.inc(stats.completed() % PROGRESS_INTERVAL);
+    Ok((connections, stats))
+}
+
+// one request and its reply (two round trips for a cas), recorded in `stats`
+async fn send_request(
+    connection: &mut Connection,
+    task: TaskData,
+    pass: &PassContext,
+    stats: &mut TaskStats,
+    buf: &mut Vec<u8>,
+) {
+    let PassContext {
+        args,
+        progress,
+        shared,
+        hits,
+        bytes,
+        cas_tokens,
+        ..
+    } = pass;
+    let max_retries = args.max_retries;
+    let validate_sequence = args.validate_sequence;
+    let recv_timeout = Duration::from_millis(args.recv_timeout_ms);
+
+    let op_start = Instant::now();
+
+    // Send
+    if !send_with_retries(connection, &task.buf, &task.addr, max_retries, stats, bytes).await {
+        stats.record_error(shared, ErrorKind::SendError);
+        stats.report_progress(progress);
+        return;
+    }
+
+    // Then receive
+    let first_reply = match (task.operation, task.cas_token) {
+        (Operation::Cas, None) => Operation::Gets,
+        _ => task.operation,
+    };
+    hits.record_sent(requested_keys(first_reply, &task));
+    let mut received = recv_reply(
+        connection,
+        first_reply,
+        buf,
+        task.seq,
+        recv_timeout,
+        validate_sequence.then_some(&mut stats.sequence),
+    )
+    .await;
+    if let Ok(Ok(amt)) = received {
+        bytes.record_received(amt);
+    }
+
+    if task.operation == Operation::Gets {
+        if let Some(token) = received
+            .as_ref()
+            .ok()
+            .and_then(|r| r.as_ref().ok())
+            .and_then(|&amt| parse_cas_token(&buf[..amt]))
+        {
+            cas_tokens.insert(&task.addr, &task.key, token);
+        }
+    }
+
+    // a cas round trip sends the cas command with the token from the gets reply,
+    // a gets miss is recorded as its END response
+    if task.operation == Operation::Cas && task.cas_token.is_none() {
+        if let Some(token) = received
+            .as_ref()
+            .ok()
+            .and_then(|r| r.as_ref().ok())
+            .and_then(|&amt| parse_cas_token(&buf[..amt]))
+        {
+            let value = &task.test_dict[&task.key];
+            let packet = wrap_cas_command(&task.key, value, args.set_flags, token, task.seq);
+            if send_with_retries(connection, &packet, &task.addr, max_retries, stats, bytes).await {
+                received = recv_reply(
+                    connection,
+                    task.operation,
+                    buf,
+                    task.seq,
+                    recv_timeout,
+                    validate_sequence.then_some(&mut stats.sequence),
+                )
+                .await;
+                if let Ok(Ok(amt)) = received {
+                    bytes.record_received(amt);
+                }
+            } else {
+                received = Ok(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
+        }
+    }
+
+    match received {
+        Ok(Ok(amt)) if is_error_reply(&buf[..amt]) => {
+            *stats
+                .responses
+                .entry(response_status(&buf[..amt]))
+                .or_default() += 1;
+            stats.record_error(shared, error_reply_kind(&buf[..amt]));
+        }
+        Ok(Ok(amt)) => {
+            let requested = match task.operation {
+                Operation::Get | Operation::Gets => 1,
+                Operation::MultiGet => task.batch.len(),
+                _ => 0,
+            };
+            if args.miss_penalty_ms > 0 && parse_value_blocks(&buf[..amt]).len() < requested {
+                tokio::time::sleep(Duration::from_millis(args.miss_penalty_ms)).await;
+            }
+            let latency = op_start.elapsed();
+            tracing::debug!(
+                operation = %task.operation.name(),
+                key = %task.key,
+                latency_us = latency.as_micros() as u64,
+                "reply"
+            );
+            stats.latencies.record(latency);
+            shared.record_reply(latency);
+            record_response(&task, &buf[..amt], stats, shared, hits);
+        }
+        Ok(Err(_)) => {
+            stats.packet_loss.record_socket_error();
+            stats.record_error(shared, ErrorKind::RecvError);
+        }
+        Err(_) => {
+            stats.packet_loss.record_timeout();
+            stats.record_error(shared, ErrorKind::Timeout);
+        }
+    }
+    stats.report_progress(progress);
+}
+
+// stream connections only: writes up to `depth` queued requests back to back before reading any reply.
+// a request's latency runs from the batch write to its own reply
+// the keys a request of `operation` reads, what the servers count in cmd_get
+fn requested_keys(operation: Operation, task: &TaskData) -> u64 {
+    match operation {
+        Operation::Get | Operation::Gets => 1,
+        Operation::MultiGet => task.batch.len() as u64,
+        _ => 0,
+    }
+}
+
+async fn pipelined_socket_task(
+    mut connections: Vec<Connection>,
+    mut rx: mpsc::Receiver<TaskData>,
+    depth: usize,
+    pass: PassContext,
+    deadline: Option<Instant>,
+) -> std::io::Result<(Vec<Connection>, TaskStats)> {
+    let PassContext {
+        args,
+        progress,
+        shared,
+        hits,
+        bytes,
+        cancel,
+        ..
+    } = pass;
+    let recv_timeout = Duration::from_millis(args.recv_timeout_ms);
+    let mut stats = TaskStats::default();
+    let mut buf = Vec::with_capacity(BUFFER_SIZE);
+
+    while let Some(task) = rx.recv().await {
+        if deadline_passed(deadline) || cancel.is_cancelled() {
+            break;
+        }
+
+        let mut batch = vec![task];
+        while batch.len() < depth {
+            match rx.try_recv() {
+                Ok(task) => batch.push(task),
+                Err(_) => break,
+            }
+        }
+
+        // each server gets its share of the batch back to back, its replies come back in order
+        let mut packets = vec![Vec::new(); connections.len()];
+        for task in batch.iter() {
+            packets[task.server].extend_from_slice(&task.buf[UDP_HEADER_SIZE..]);
+        }
+
+        let batch_start = Instant::now();
+        let mut failed = vec![false; connections.len()];
+        for (server, packets) in packets.iter().enumerate() {
+            if packets.is_empty() {
+                continue;
+            }
+            match connections[server].write_commands(packets).await {
+                Ok(()) => bytes.record_sent(packets.len()),
+                Err(_) => failed[server] = true,
+            }
+        }
+        // the requests of a failed write never reached their server, nothing to wait for
+        batch.retain(|task| {
+            if failed[task.server] {
+                stats.record_error(&shared, ErrorKind::SendError);
+                stats.report_progress(&progress);
+            }
+            !failed[task.server]
+        });
+        for task in batch.iter() {
+            hits.record_sent(requested_keys(task.operation, task));
+        }
+
+        for task in batch.iter() {
+            let connection = &mut connections[task.server];
+            let received = timeout(recv_timeout, connection.recv(task.operation, &mut buf)).await;
+            if let Ok(Ok(amt)) = received {
+                bytes.record_received(amt);
+            }
+            match received {
+                Ok(Ok(amt)) if is_error_reply(&buf[..amt]) => {
+                    *stats
+                        .responses
+                        .entry(response_status(&buf[..amt]))
+                        .or_default() += 1;
+                    stats.record_error(&shared, error_reply_kind(&buf[..amt]));
+                }
+                Ok(Ok(amt)) => {
+                    let latency = batch_start.elapsed();
+                    tracing::debug!(
+                        operation = %task.operation.name(),
+                        key = %task.key,
+                        latency_us = latency.as_micros() as u64,
+                        "reply"
+                    );
+                    stats.latencies.record(latency);
+                    shared.record_reply(latency);
+                    record_response(task, &buf[..amt], &mut stats, &shared, &hits);
+                }
+                Ok(Err(_)) => {
+                    stats.packet_loss.record_socket_error();
+                    stats.record_error(&shared, ErrorKind::RecvError);
+                }
+                Err(_) => {
+                    stats.packet_loss.record_timeout();
+                    stats.record_error(&shared, ErrorKind::Timeout);
+                }
+            }
+            stats.report_progress(&progress);
+        }
+    }
+
+    progress.inc(stats.completed() % PROGRESS_INTERVAL);
+    Ok((connections, stats))
+}
+
+// what one benchmark thread measured, printed as its own row before the pass aggregate
+struct ThreadStats {
+    thread: usize,
+    stats: TaskStats,
+}
+
+impl ThreadStats {
+    fn print(
+        &self,
+        out: &mut impl Write,
+        operation: Operation,
+        validate_sequence: bool,
+    ) -> std::io::Result<()> {
+        let stats = &self.stats;
+        let name = format!(
+            "thread {} {}_command_benchmark()",
+            self.thread,
+            operation.name()
+        );
+        writeln!(
+            out,
+            "Time elapsed in {} is: {:?}, {} ops, errors: {}, throughput: {:.2} ops/sec",
+            name,
+            stats.duration,
+            stats.latencies.len(),
+            stats.errors,
+            stats.latencies.len() as f64 / stats.duration.as_secs_f64(),
+        )?;
+        stats.latencies.print(out, &name)?;
+        stats.print_responses(out, &name)?;
+        if validate_sequence {
+            stats.sequence.print(out, &name)?;
+        }
+        Ok(())
+    }
+}
+
+// shared by every thread of one benchmark pass
+#[derive(Clone)]
+struct PassContext {
+    args: Arc<Cli>,
+    // host:port of every server
+    addrs: Arc<Vec<String>>,
+    // indices into addrs, the requests of every thread rotate over them
+    schedule: Arc<Vec<usize>>,
+    test_dict: Arc<HashMap<String, Vec<u8>>>,
+    // --trace-file keys, replayed instead of sampling
+    trace: Option<Arc<Vec<String>>>,
+    // hidden unless the output format is text
+    progress: ProgressBar,
+    // replies, failures and latency extremes of the whole run, across every pass
+    shared: Arc<BenchmarkStats>,
+    // get hits and misses of this pass
+    hits: HitCounter,
+    // the gets of its warmup and cooldown requests, the servers count them as well
+    unmeasured_hits: HitCounter,
+    // protocol bytes sent and received during this pass
+    bytes: ByteCounter,
+    cas_tokens: CasTokenStore,
+    // -l tls
+    tls: Option<Arc<ClientConfig>>,
+    // cancelled by --abort-on-error-rate, every thread stops sending
+    cancel: CancellationToken,
+}
+
+impl PassContext {
+    // the same pass with counters of its own, for the warmup and cooldown requests that stay
+    // out of the reported stats and the metrics
+    fn unmeasured(&self) -> PassContext {
+        PassContext {
+            shared: Arc::new(BenchmarkStats::default()),
+            hits: self.unmeasured_hits.clone(),
+            bytes: ByteCounter::default(),
+            ..self.clone()
+        }
+    }
+}
+
+// everything one benchmark thread needs to generate its requests
+struct ThreadContext {
+    pass: PassContext,
+    // index into the pass schedule of the server the next request goes to
+    next_server: usize,
+    operation: Operation,
+    // requests written before reading replies, only honoured over TCP
+    pipeline_depth: usize,
+    rng: StdRng,
+}
+
+// polls how many requests wait in the channel until every sender is gone, returns the peak.
+// it runs on the blocking pool: a task spawned next to the sender would sit in the
+// runtime's lifo slot and only get polled once the sender stops to wait
+fn sample_channel_occupancy(tx: mpsc::WeakSender<TaskData>) -> usize {
+    let mut peak = 0;
+    loop {
+        match tx.upgrade() {
+            Some(tx) => peak = peak.max(tx.max_capacity() - tx.capacity()),
+            None => return peak,
+        }
+        std::thread::sleep(CHANNEL_SAMPLE_INTERVAL);
+    }
+}
+
+// how run_requests hands its requests to the connection
+enum Dispatch {
+    // queued for the socket task, so the next request is generated while one is in flight
+    Channel {
+        tx: mpsc::Sender<TaskData>,
+        socket_task: JoinHandle<std::io::Result<(Vec<Connection>, TaskStats)>>,
+        sampler_task: JoinHandle<usize>,
+    },
+    // --closed-loop: sent right away, the next request waits for this one's reply
+    Inline {
+        connections: Vec<Connection>,
+        stats: Box<TaskStats>,
+        buf: Vec<u8>,
+    },
+}
+
+// sends `count` requests over `connections`, one to each server, and hands them back for
+// the next round
+async fn run_requests(
+    ctx: &mut ThreadContext,
+    connections: Vec<Connection>,
+    count: usize,
+    deadline: Option<Instant>,
+) -> Result<(Vec<Connection>, TaskStats), Box<dyn Error>> {
+    let ThreadContext {
+        pass,
+        next_server,
+        operation,
+        pipeline_depth,
+        rng,
+    } = ctx;
+    let socket_pass = pass.clone();
+    let PassContext {
+        args,
+        addrs,
+        schedule,
+        test_dict,
+        trace,
+        cas_tokens,
+        cancel,
+        ..
+    } = pass;
+    let (operation, pipeline_depth) = (*operation, *pipeline_depth);
+
+    // HashMap order differs between runs, sort so a seed always maps to the same keys
+    let mut keys: Vec<&String> = test_dict.keys().collect();
+    keys.sort();
+    // a trace names its own keys
+    if let (Some(size), None) = (args.working_set_size, trace.as_ref()) {
+        keys.truncate(size as usize);
+    }
+    let mut sampler = new_key_sampler(args.key_distribution, keys.len(), args.zipf_exponent)?;
+    let key_sizes = args.key_size_distribution()?;
+    if trace.is_none() && key_sizes.sizes().start() != key_sizes.sizes().end() {
+        let sizes: Vec<usize> = keys
+            .iter()
+            .map(|key| key.len().saturating_sub(args.key_prefix.len()))
+            .collect();
+        sampler = by_key_size(args.key_distribution, args.zipf_exponent, &sizes, key_sizes)?;
+    }
+    if let Some(trace) = trace {
+        let index: HashMap<&str, usize> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), i))
+            .collect();
+        sampler = new_trace_sampler(trace.iter().map(|key| index[key.as_str()]).collect());
+    } else if args.hot_key_fraction > 0.0 && args.hot_key_ratio > 0.0 {
+        let seed = args.seed.unwrap_or_default();
+        sampler = with_hot_keys(
+            sampler,
+            keys.len(),
+            args.hot_key_fraction,
+            args.hot_key_ratio,
+            seed,
+        );
+    }
+
+    let mut seq: u16 = 0;
+
+    let mut dispatch = match args.closed_loop {
+        true => Dispatch::Inline {
+            connections,
+            stats: Box::default(),
+            buf: Vec::with_capacity(BUFFER_SIZE),
+        },
+        false => {
+            // Create the channel
+            let (tx, rx) = mpsc::channel(args.channel_capacity as usize);
+            let weak_tx = tx.downgrade();
+            let sampler_task =
+                tokio::task::spawn_blocking(move || sample_channel_occupancy(weak_tx));
+            let socket_task = match connections[0] {
+                Connection::Tcp(..) | Connection::Unix(..) | Connection::Tls(..)
+                    if pipeline_depth > 1 =>
+                {
+                    tokio::spawn(pipelined_socket_task(
+                        connections,
+                        rx,
+                        pipeline_depth,
+                        socket_pass.clone(),
+                        deadline,
+                    ))
+                }
+                _ => tokio::spawn(socket_task(connections, rx, socket_pass.clone(), deadline)),
+            };
+            Dispatch::Channel {
+                tx,
+                socket_task,
+                sampler_task,
+            }
+        }
+    };
+
+    // --rate is split evenly across the benchmark threads
+    let ramping = args.rate > 0 && args.ramp_duration_s > 0;
+    let mut ticker = (args.rate > 0 && !ramping).then(|| {
+        tokio::time::interval(Duration::from_secs_f64(
+            args.threads as f64 / args.rate as f64,
+        ))
+    });
+    let ramp_start = tokio::time::Instant::now();
+    let mut next_send = ramp_start;
+
+    for sent in 1..=count {
+        // breaking out drops the sender, so the socket task ends once the queue is drained
+        if deadline_passed(deadline) || cancel.is_cancelled() {
+            break;
+        }
+        if ramping {
+            tokio::time::sleep_until(next_send).await;
+            next_send += ramp_period(args, ramp_start.elapsed());
+        } else if let Some(ticker) = ticker.as_mut() {
+            ticker.tick().await;
+        }
+
+        let mut key = keys[sampler.sample(rng)].clone();
+        // round-robin over the servers, request by request
+        let server = schedule[*next_server];
+        *next_server = (*next_server + 1) % schedule.len();
+        let addr = &addrs[server];
+
+        // mixed workload: the get pass issues a SET for the non-read fraction
+        let operation = match operation {
+            Operation::Get if rng.gen::<f64>() >= args.read_ratio => Operation::Set,
+            _ => operation,
+        };
+        let ttl = sample_ttl(rng, args.ttl, args.ttl_jitter);
+        let mut batch = vec![];
+        let cas_token = match operation {
+            Operation::Cas => cas_tokens.take(addr, &key),
+            _ => None,
+        };
+        let packet = match operation {
+            Operation::MultiGet => {
+                batch.push(key.clone());
+                for _ in 1..args.batch_size {
+                    batch.push(keys[sampler.sample(rng)].clone());
+                }
+                let batch: Vec<&str> = batch.iter().map(|k| k.as_str()).collect();
+                wrap_multi_get_command(&batch, seq)
+            }
+            // the new key keeps the value of the sampled one
+            Operation::Add if args.add_new_keys => {
+                let new_key = format!(
+                    "{}{}",
+                    args.key_prefix,
+                    generate_random_str(rng, args.key_sizes().end() + 1)
+                );
+                let packet = wrap_add_command(&new_key, &test_dict[&key], args.set_flags, ttl, seq);
+                key = new_key;
+                packet
+            }
+            Operation::Cas if let Some(token) = cas_token => {
+                wrap_cas_command(&key, &test_dict[&key], args.set_flags, token, seq)
+            }
+            _ => operation.wrap_command(&key, &test_dict[&key], args.set_flags, ttl, seq),
+        };
+
+        let task = TaskData {
+            buf: packet,
+            server,
+            addr: addr.clone(),
+            key,
+            batch,
+            seq,
+            operation,
+            test_dict: test_dict.clone(),
+            validate: args.validate,
+            integrity_hash: args.integrity_hash,
+            cas_token,
+        };
+        seq = seq.wrapping_add(1);
+        match &mut dispatch {
+            Dispatch::Inline {
+                connections,
+                stats,
+                buf,
+            } => send_request(&mut connections[server], task, &socket_pass, stats, buf).await,
+            Dispatch::Channel { tx, .. } => {
+                if tx.send(task).await.is_err() {
+                    // The receiver was dropped, break the loop
+                    break;
+                }
+            }
+        }
+
+        let think_time = sample_think_time(rng, args.think_time_us, args.think_time_jitter_us);
+        if !think_time.is_zero() {
+            tokio::time::sleep(think_time).await;
+        }
+        if args.burst_size > 0 && sent.is_multiple_of(args.burst_size) {
+            tokio::time::sleep(Duration::from_millis(args.burst_interval_ms)).await;
+        }
+    }
+
+    match dispatch {
+        Dispatch::Inline {
+            connections, stats, ..
+        } => {
+            socket_pass
+                .progress
+                .inc(stats.completed() % PROGRESS_INTERVAL);
+            Ok((connections, *stats))
+        }
+        Dispatch::Channel {
+            tx,
+            socket_task,
+            sampler_task,
+        } => {
+            // Close the channel
+            drop(tx);
+
+            // Wait for the socket task to finish
+            let (connections, mut stats) = socket_task.await??;
+            stats.peak_channel_occupancy = sampler_task.await?;
+            Ok((connections, stats))
+        }
+    }
+}
+
+async fn command_benchmark(
+    pass: PassContext,
+    thread: usize,
+    operation: Operation,
+    pipeline_depth: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    let args = Arc::clone(&pass.args);
+
+    // each thread draws its own reproducible stream from the run seed,
+    // offset by one so no thread replays the stream that generated the dataset
+    let seed = args
+        .seed
+        .unwrap_or_default()
+        .wrapping_add(thread as u64 + 1);
+
+    // every benchmark thread opens its own connection to each server so sends and receives
+    // run in parallel. the threads start their rotation at different servers
+    let mut connections = vec![];
+    for addr in pass.addrs.iter() {
+        connections.push(Connection::connect(&args, addr, pass.tls.as_ref()).await?);
+    }
+    let mut ctx = ThreadContext {
+        next_server: thread % pass.schedule.len(),
+        pass,
+        operation,
+        pipeline_depth,
+        rng: StdRng::seed_from_u64(seed),
+    };
+
+    // warm up over the same connections so their setup cost stays out of the measurement
+    if args.warmup_ops > 0 && args.warmup_threads.is_none() {
+        connections = run_unmeasured(&mut ctx, connections, args.warmup_ops).await?;
+    }
+
+    let start = Instant::now();
+    // with --duration the deadline ends the pass, the request count is unbounded
+    let (count, deadline) = match args.duration {
+        Some(secs) => (usize::MAX, Some(start + Duration::from_secs(secs))),
+        None => (args.nums, None),
+    };
+    let (connections, mut stats) = run_requests(&mut ctx, connections, count, deadline).await?;
+
+    stats.duration = start.elapsed();
+    if args.cooldown_ops > 0 {
+        run_unmeasured(&mut ctx, connections, args.cooldown_ops).await?;
+    }
+    Ok(stats)
+}
+
+// sends `count` requests whose replies only count toward the progress bar, the thread's rng
+// carries on so the measured requests of a seed stay the same
+async fn run_unmeasured(
+    ctx: &mut ThreadContext,
+    connections: Vec<Connection>,
+    count: usize,
+) -> Result<Vec<Connection>, Box<dyn Error>> {
+    let unmeasured = ctx.pass.unmeasured();
+    let measured = std::mem::replace(&mut ctx.pass, unmeasured);
+    let result = run_requests(ctx, connections, count, None).await;
+    ctx.pass = measured;
+    Ok(result?.0)
+}
+
+async fn pipelined_get_benchmark(
+    pass: PassContext,
+    thread: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    let depth = pass.args.pipeline_depth as usize;
+    command_benchmark(pass, thread, Operation::Get, depth).await
+}
+
+async fn multi_get_benchmark(
+    pass: PassContext,
+    thread: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    let depth = pass.args.pipeline_depth as usize;
+    command_benchmark(pass, thread, Operation::MultiGet, depth).await
+}
+
+async fn get_command_benchmark(
+    pass: PassContext,
+    thread: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Get, 1).await
+}
+
+async fn set_command_benchmark(
+    pass: PassContext,
+    thread: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Set, 1).await
+}
+
+async fn delete_command_benchmark(
+    pass: PassContext,
+    thread: usize,
+) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Delete, 1).await
+}
+
+async fn counter_benchmark(
+    pass: PassContext,
+    thread: usize,
+    operation: Operation,
+) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, operation, 1).await
+}
+
+async fn prepend_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Prepend, 1).await
+}
+
+async fn append_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Append, 1).await
+}
+
+async fn replace_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Replace, 1).await
+}
+
+async fn add_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Add, 1).await
+}
+
+async fn gets_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Gets, 1).await
+}
+
+async fn cas_benchmark(pass: PassContext, thread: usize) -> Result<TaskStats, Box<dyn Error>> {
+    command_benchmark(pass, thread, Operation::Cas, 1).await
+}
+
+// one thread of a pass, a failure is logged and leaves the thread out of the results
+async fn benchmark_thread(
+    pass: PassContext,
+    thread: usize,
+    operation: Operation,
+) -> Option<ThreadStats> {
+    let args = Arc::clone(&pass.args);
+    let result = match operation {
+        Operation::Get if args.batch_size > 1 => multi_get_benchmark(pass, thread).await,
+        Operation::Get if args.pipeline_depth > 1 => pipelined_get_benchmark(pass, thread).await,
+        Operation::Get => get_command_benchmark(pass, thread).await,
+        Operation::Set => set_command_benchmark(pass, thread).await,
+        Operation::Delete => delete_command_benchmark(pass, thread).await,
+        Operation::Incr | Operation::Decr => counter_benchmark(pass, thread, operation).await,
+        Operation::Cas => cas_benchmark(pass, thread).await,
+        Operation::Prepend => prepend_benchmark(pass, thread).await,
+        Operation::Append => append_benchmark(pass, thread).await,
+        Operation::Replace => replace_benchmark(pass, thread).await,
+        Operation::Add => add_benchmark(pass, thread).await,
+        Operation::Gets => gets_benchmark(pass, thread).await,
+        Operation::MultiGet => {
+            unreachable!("{:?} is not selectable from the command line", operation)
+        }
+    };
+    match result {
+        Ok(stats) => Some(ThreadStats { thread, stats }),
+        Err(e) => {
+            tracing::error!(thread, error = ?e, "task failed");
+            None
+        }
+    }
+}
+
+// sequential GET of every key in the dataset over a fresh connection, exact value match.
+// mismatches also count towards the validation mismatches of `shared`
+async fn consistency_check(
+    args: &Cli,
+    addr: &str,
+    test_dict: &HashMap<String, Vec<u8>>,
+    tls: Option<&Arc<ClientConfig>>,
+    shared: &BenchmarkStats,
+) -> Result<ConsistencySummary, Box<dyn Error>> {
+    let mut connection = Connection::connect(args, addr, tls).await?;
+    let mut summary = ConsistencySummary::default();
+    let mut buf = Vec::with_capacity(BUFFER_SIZE);
+
+    let recv_timeout = Duration::from_millis(args.recv_timeout_ms);
+    let mut keys: Vec<&String> = test_dict.keys().collect();
+    keys.sort();
+    for (seq, key) in keys.into_iter().enumerate() {
+        let seq = seq as u16;
+        connection.send(&wrap_get_command(key, seq), addr).await?;
+        let received = recv_reply(
+            &mut connection,
+            Operation::Get,
+            &mut buf,
+            seq,
+            recv_timeout,
+            None,
+        )
+        .await;
+        let amt = match received {
+            Ok(Ok(amt)) => amt,
+            _ => {
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        match parse_value_blocks(&buf[..amt]).first() {
+            Some((_, data)) if *data == test_dict[key] => summary.verified += 1,
+            Some((_, data)) => {
+                summary.mismatches += 1;
+                shared.record_validation_mismatch();
+                tracing::warn!(
+                    key = %key,
+                    buf = %String::from_utf8_lossy(data),
+                    value = %String::from_utf8_lossy(&test_dict[key]),
+                    "consistency check mismatch"
+                );
+            }
+            None => summary.missing += 1,
+        }
+    }
+
+    Ok(summary)
+}
+
+// --key-boundary-test: one set and get per length of KEY_BOUNDARY_LENGTHS. the keys leave out
+// --key-prefix so every length is exact, and every key gets a connection of its own: a server
+// rejecting the key line may go on to read the data block as a command
+async fn key_boundary_test(
+    args: &Cli,
+    addr: &str,
+    tls: Option<&Arc<ClientConfig>>,
+    rng: &mut impl Rng,
+) -> Result<Vec<KeyBoundaryResult>, Box<dyn Error>> {
+    let recv_timeout = Duration::from_millis(args.recv_timeout_ms);
+    let mut buf = Vec::with_capacity(BUFFER_SIZE);
+    let mut results = vec![];
+    for key_length in KEY_BOUNDARY_LENGTHS {
+        let key = generate_random_str(rng, key_length);
+        let mut connection = Connection::connect(args, addr, tls).await?;
+
+        connection
+            .send(&wrap_set_command(&key, KEY_BOUNDARY_VALUE, 0, 0, 0), addr)
+            .await?;
+        let received = recv_reply(
+            &mut connection,
+            Operation::Set,
+            &mut buf,
+            0,
+            recv_timeout,
+            None,
+        );
+        let reply = match received.await {
+            Ok(amt) => {
+                let amt = amt?;
+                let reply = String::from_utf8_lossy(&buf[..amt]);
+                reply.lines().next().unwrap_or_default().to_string()
+            }
+            Err(_) => "timeout".to_string(),
+        };
+        let stored = reply == "STORED";
+
+        let mut read_back = false;
+        if stored {
+            connection.send(&wrap_get_command(&key, 1), addr).await?;
+            let received = recv_reply(
+                &mut connection,
+                Operation::Get,
+                &mut buf,
+                1,
+                recv_timeout,
+                None,
+            );
+            if let Ok(amt) = received.await {
+                read_back = parse_value_blocks(&buf[..amt?])
+                    .first()
+                    .is_some_and(|(got, data)| *got == key && *data == KEY_BOUNDARY_VALUE);
+            }
+        }
+        results.push(KeyBoundaryResult {
+            server: addr.to_string(),
+            key_length,
+            reply,
+            stored,
+            read_back,
+        });
+    }
+    Ok(results)
+}
+
+// --interval-ms: wakes every `interval` until aborted and prints the ops/sec, errors and
+// mean latency of the pass since the last wake, as a json object per line with
+// --output-format json. the shared counters run over the whole run, so every value is
+// taken relative to the previous wake
+async fn interval_reporter(
+    shared: Arc<BenchmarkStats>,
+    operation: Operation,
+    interval: Duration,
+    output_format: OutputFormat,
+    progress: ProgressBar,
+    mut out: Writer,
+) {
+    let start = Instant::now();
+    let mut ticker = tokio::time::interval(interval);
+    // a reporter held up by busy workers skips the missed ticks instead of firing them
+    // back to back, which would print near-empty intervals
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // the first tick completes immediately
+    ticker.tick().await;
+    let pass_start_ops = shared.ops();
+    let (mut last_ops, mut last_errors, mut last_latency_ns, mut last_tick) =
+        (pass_start_ops, shared.errors(), shared.latency_ns(), start);
+    let (mut last_timeouts, mut last_recv_errors) = (
+        shared.error_count(ErrorKind::Timeout),
+        shared.error_count(ErrorKind::RecvError),
+    );
+    loop {
+        ticker.tick().await;
+        let now = Instant::now();
+        let (total_ops, total_errors, total_latency_ns) =
+            (shared.ops(), shared.errors(), shared.latency_ns());
+        let (total_timeouts, total_recv_errors) = (
+            shared.error_count(ErrorKind::Timeout),
+            shared.error_count(ErrorKind::RecvError),
+        );
+        let interval_ops = total_ops - last_ops;
+        let sample = IntervalSample {
+            timestamp_ms: unix_time_ms(),
+            elapsed_ms: now.duration_since(start).as_millis() as u64,
+            operation: operation.name(),
+            ops: total_ops - pass_start_ops,
+            ops_per_sec: interval_ops as f64 / now.duration_since(last_tick).as_secs_f64(),
+            errors: total_errors - last_errors,
+            timeouts: total_timeouts - last_timeouts,
+            recv_errors: total_recv_errors - last_recv_errors,
+            mean_latency_us: match interval_ops {
+                0 => 0.0,
+                ops => (total_latency_ns - last_latency_ns) as f64 / ops as f64 / 1000.0,
+            },
+        };
+        (last_ops, last_errors, last_latency_ns, last_tick) =
+            (total_ops, total_errors, total_latency_ns, now);
+        (last_timeouts, last_recv_errors) = (total_timeouts, total_recv_errors);
+
+        // keeps the line from being drawn over by the progress bar
+        progress.suspend(|| match output_format {
+            OutputFormat::Json => match serde_json::to_string(&sample) {
+                // stdout only holds the report, the sample goes with the other diagnostics
+                Ok(line) => tracing::info!(sample = %line, "interval"),
+                Err(e) => tracing::warn!(error = %e, "failed to encode interval sample"),
+            },
+            // streamed into the results, the summary line follows at the end of the run
+            OutputFormat::Ndjson => {
+                let written = serde_json::to_string(&sample)
+                    .map_err(Box::<dyn Error>::from)
+                    .and_then(|line| Ok(writeln!(out, "{}", line)?));
+                if let Err(e) = written {
+                    tracing::warn!(error = %e, "failed to write interval sample");
+                }
+            }
+            _ => tracing::info!(
+                elapsed_ms = sample.elapsed_ms,
+                operation = %sample.operation,
+                ops_per_sec = format_args!("{:.2}", sample.ops_per_sec),
+                mean_latency_us = format_args!("{:.1}", sample.mean_latency_us),
+                errors = sample.errors,
+                timeouts = sample.timeouts,
+                recv_errors = sample.recv_errors,
+                ops = sample.ops,
+                "interval"
+            ),
+        });
+    }
+}
+
+// --abort-on-error-rate: wakes every second and cancels the run once more than `threshold`
+// of its requests failed
+async fn error_rate_monitor(
+    shared: Arc<BenchmarkStats>,
+    threshold: f64,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(ERROR_RATE_CHECK_INTERVAL);
+    // the first tick completes immediately
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let (ops, errors) = (shared.ops(), shared.errors());
+        let completed = ops + errors;
+        if completed == 0 {
+            continue;
+        }
+        let error_rate = errors as f64 / completed as f64;
+        if error_rate > threshold {
+            tracing::error!(
+                errors,
+                requests = completed,
+                "error rate {:.2}% exceeds --abort-on-error-rate {:.2}%, aborting the benchmark",
+                error_rate * 100.0,
+                threshold * 100.0
+            );
+            cancel.cancel();
+            return;
+        }
+    }
+}
+
+// what stays the same over every run of the benchmark, and over both protocols with
+// --compare-protocols
+struct Benchmark {
+    addrs: Arc<Vec<String>>,
+    schedule: Arc<Vec<usize>>,
+    servers: Vec<memcache::Client>,
+    test_dict: Arc<HashMap<String, Vec<u8>>>,
+    trace: Option<Arc<Vec<String>>>,
+    tls: Option<Arc<ClientConfig>>,
+    metrics: Option<metrics::Metrics>,
+    cas_tokens: CasTokenStore,
+    cancel: CancellationToken,
+    palette: table::Palette,
+    seed: u64,
+    // the server stats from before the dataset was stored
+    population_snapshots: Vec<HashMap<String, String>>,
+    // --output-format hdr: the intervals start relative to it
+    log_start: std::time::SystemTime,
+}
+
+// what one pass measured
+struct PassReport {
+    duration: Duration,
+    total: TaskStats,
+    hits: HitCounter,
+    // warmup and cooldown gets
+    unmeasured_gets: u64,
+    bytes: ByteCounter,
+    consistency: Option<ConsistencySummary>,
+}
+
+// what one run measured, its summary is printed from it
+struct RunReport {
+    total: TaskStats,
+    shared: Arc<BenchmarkStats>,
+    hits: HitCounter,
+    bytes: ByteCounter,
+    duration: Duration,
+    operations: Vec<OperationSummary>,
+    // the server stats once the run is done
+    snapshots: Vec<HashMap<String, String>>,
+    server_stats: Vec<ServerStatsDelta>,
+    memory: Vec<MemoryDelta>,
+    evictions: Option<Vec<EvictionSummary>>,
+}
+
+// one run: a pass of every --operation, then the server stats over the run
+async fn execute_run(
+    bench: &Benchmark,
+    args: &Arc<Cli>,
+    out: &mut Writer,
+    hdr_intervals: &mut Vec<hdr::Interval>,
+) -> Result<RunReport, Box<dyn Error>> {
+    let Benchmark {
+        addrs,
+        servers,
+        metrics,
+        cancel,
+        ..
+    } = bench;
+    // taken once the dataset is stored, so the delta only covers the benchmark passes
+    // (and the counter reset before an incr/decr pass)
+    let snapshots = take_stats_snapshots(servers).await?;
+
+    let mut total = TaskStats::default();
+    let shared = Arc::new(BenchmarkStats::with_metrics(metrics.clone()));
+    let monitor = args.abort_on_error_rate.map(|threshold| {
+        tokio::spawn(error_rate_monitor(
+            Arc::clone(&shared),
+            threshold,
+            cancel.clone(),
+        ))
+    });
+    let hits = HitCounter::default();
+    let bytes = ByteCounter::default();
+    let mut duration = Duration::ZERO;
+    let mut operations = vec![];
+    let mut unmeasured_gets = 0;
+
+    // by default a timed SET pass runs first, then the GET pass reads the values back
+    for &operation in args.operation.iter() {
+        let pass = execute_pass(bench, args, operation, &shared, out, hdr_intervals).await?;
+        operations.push(OperationSummary {
+            operation: operation.name(),
+            summary: Summary {
+                hit_rate: pass.hits.hit_rate(),
+                ..pass
+                    .total
+                    .summary(pass.duration)
+                    .with_bytes(&pass.bytes, pass.duration)
+            },
+            consistency: pass.consistency,
+        });
+        hits.merge(&pass.hits);
+        unmeasured_gets += pass.unmeasured_gets;
+        bytes.merge(&pass.bytes);
+        total.merge(&pass.total);
+        duration += pass.duration;
+    }
+
+    let afters = take_stats_snapshots(servers).await?;
+    let evictions = args.measure_evictions.then(|| {
+        addrs
+            .iter()
+            .zip(snapshots.iter().zip(afters.iter()))
+            .map(|(addr, (before, after))| eviction_summary(addr, before, after, duration))
+            .collect::<Vec<_>>()
+    });
+    let memory = addrs
+        .iter()
+        .zip(bench.population_snapshots.iter().zip(afters.iter()))
+        .map(|(addr, (before, after))| memory_delta(addr, before, after))
+        .collect::<Vec<_>>();
+    for delta in memory.iter().filter(|delta| delta.memory_added_bytes < 0) {
+        tracing::warn!(
+            server = %delta.server,
+            memory_added_bytes = delta.memory_added_bytes,
+            "the server holds less memory than before the dataset was stored, it may have evicted items"
+        );
+    }
+    // fewer gets on the servers than sent were dropped on the way, more come from
+    // another client. the consistency check reads every key through its own connection
+    let server_gets: i64 = snapshots
+        .iter()
+        .zip(afters.iter())
+        .map(|(before, after)| field_delta(before, after, "cmd_get"))
+        .sum();
+    let client_gets = hits.sent()
+        + unmeasured_gets
+        + operations
+            .iter()
+            .filter_map(|o| o.consistency.as_ref())
+            .map(|c| (c.verified + c.mismatches + c.missing + c.errors) as u64)
+            .sum::<u64>();
+    if server_gets != client_gets as i64 {
+        tracing::warn!(
+            server_cmd_get = server_gets,
+            client_gets,
+            "the servers counted a different number of gets than the benchmark sent"
+        );
+    }
+    let server_stats: Vec<_> = addrs
+        .iter()
+        .zip(snapshots.iter().zip(afters.iter()))
+        .map(|(addr, (before, after))| ServerStatsDelta {
+            server: addr.clone(),
+            deltas: stats_deltas(before, after, &STATS_DELTA_FIELDS)
+                .into_iter()
+                .collect(),
+            evicted_items: field_delta(before, after, "evictions"),
+            expired_items: field_delta(before, after, "expired_unfetched"),
+        })
+        .collect();
+    // evictions mean the dataset exceeds the server's memory, items that expired
+    // before anyone read them mean the ttls are too short for the access pattern
+    for stats in server_stats.iter() {
+        let sets = stats.deltas.get("cmd_set").copied().unwrap_or_default();
+        let share = |items: i64| items as f64 / sets.max(1) as f64;
+        if share(stats.evicted_items) > ITEM_LOSS_WARN_RATIO {
+            tracing::warn!(
+                server = %stats.server,
+                evicted_items = stats.evicted_items,
+                sets,
+                "more than {:.0}% of the sets of the run were evicted, the dataset exceeds the server's memory",
+                ITEM_LOSS_WARN_RATIO * 100.0
+            );
+        }
+        if share(stats.expired_items) > ITEM_LOSS_WARN_RATIO {
+            tracing::warn!(
+                server = %stats.server,
+                expired_items = stats.expired_items,
+                sets,
+                "more than {:.0}% of the sets of the run expired unfetched, the ttls are too short for the access pattern",
+                ITEM_LOSS_WARN_RATIO * 100.0
+            );
+        }
+    }
+
+    // evicted keys miss on the next get, which the hit rate would blame on the dataset
+    for eviction in evictions.iter().flatten().filter(|e| e.evictions > 0) {
+        tracing::warn!(
+            server = %eviction.server,
+            evictions = eviction.evictions,
+            "the server evicted keys during the run, the dataset does not fit in its memory"
+        );
+    }
+
+    if let Some(monitor) = monitor {
+        monitor.abort();
+    }
+    Ok(RunReport {
+        total,
+        shared,
+        hits,
+        bytes,
+        duration,
+        operations,
+        snapshots: afters,
+        server_stats,
+        memory,
+        evictions,
+    })
+}
+
+// one pass of `operation`: its warmup, every benchmark thread and the consistency check
+async fn execute_pass(
+    bench: &Benchmark,
+    args: &Arc<Cli>,
+    operation: Operation,
+    shared: &Arc<BenchmarkStats>,
+    out: &mut Writer,
+    hdr_intervals: &mut Vec<hdr::Interval>,
+) -> Result<PassReport, Box<dyn Error>> {
+    let Benchmark {
+        addrs,
+        schedule,
+        servers,
+        test_dict,
+        trace,
+        tls,
+        metrics,
+        cas_tokens,
+        cancel,
+        palette,
+        seed,
+        log_start,
+        ..
+    } = bench;
+    if matches!(operation, Operation::Incr | Operation::Decr) {
+        for server in servers.iter() {
+            let (server, test_dict) = (server.clone(), test_dict.clone());
+            tokio::task::spawn_blocking(move || set_counter_values(&server, test_dict)).await??;
+        }
+    }
+
+    // with --warmup-threads the warmup is a phase of its own, before the progress bar
+    let warmup_ops = match args.warmup_threads {
+        Some(_) => 0,
+        None => args.warmup_ops,
+    };
+    let progress = match args.output_format.is_human() {
+        true => new_progress_bar(
+            args.duration
+                .is_none()
+                .then(|| (args.threads * (warmup_ops + args.nums + args.cooldown_ops)) as u64),
+        ),
+        false => ProgressBar::hidden(),
+    };
+    let pass = PassContext {
+        args: Arc::clone(args),
+        addrs: Arc::clone(addrs),
+        schedule: Arc::clone(schedule),
+        test_dict: Arc::clone(test_dict),
+        trace: trace.clone(),
+        progress: progress.clone(),
+        shared: Arc::clone(shared),
+        hits: HitCounter::default(),
+        unmeasured_hits: HitCounter::default(),
+        bytes: ByteCounter::with_metrics(metrics.clone()),
+        cas_tokens: cas_tokens.clone(),
+        tls: tls.clone(),
+        cancel: cancel.clone(),
+    };
+
+    let reporter = (args.interval_ms > 0).then(|| {
+        tokio::spawn(interval_reporter(
+            Arc::clone(shared),
+            operation,
+            Duration::from_millis(args.interval_ms),
+            args.output_format,
+            progress.clone(),
+            out.clone(),
+        ))
+    });
+
+    // every warmup thread has to finish before the timed threads start
+    if let (Some(warmup_threads), true) = (args.warmup_threads, args.warmup_ops > 0) {
+        let warmup = PassContext {
+            args: Arc::new(Cli {
+                threads: warmup_threads as usize,
+                nums: args.warmup_ops,
+                duration: None,
+                cooldown_ops: 0,
+                ..Cli::clone(args)
+            }),
+            progress: ProgressBar::hidden(),
+            ..pass.unmeasured()
+        };
+        let handles: Vec<_> = (0..warmup_threads as usize)
+            .map(|thread| tokio::spawn(benchmark_thread(warmup.clone(), thread, operation)))
+            .collect();
+        let mut total = TaskStats::default();
+        for handle in handles {
+            if let Some(thread) = handle.await? {
+                total.merge(&thread.stats);
+            }
+        }
+        let ops_per_sec = total.latencies.len() as f64 / total.duration.as_secs_f64();
+        match args.output_format.is_human() {
+            true => writeln!(
+                out,
+                "warmup {}: {} ops in {:?} over {} threads, throughput: {:.2} ops/sec",
+                operation.name(),
+                total.latencies.len(),
+                total.duration,
+                warmup_threads,
+                ops_per_sec
+            )?,
+            false => tracing::info!(
+                operation = %operation.name(),
+                ops = total.latencies.len(),
+                ops_per_sec,
+                "warmup"
+            ),
+        }
+    }
+
+    let pass_start = std::time::SystemTime::now();
+    let mut handles = vec![];
+
+    for thread in 0..args.threads {
+        let pass = pass.clone();
+        let handle = tokio::spawn(benchmark_thread(pass, thread, operation));
+        handles.push(handle);
+    }
+
+    // wait for all tasks to complete and aggregate their stats
+    let mut threads = vec![];
+    let mut total = TaskStats::default();
+    for handle in handles {
+        if let Some(thread) = handle.await? {
+            total.merge(&thread.stats);
+            threads.push(thread);
+        }
+    }
+
+    if let Some(reporter) = reporter {
+        reporter.abort();
+    }
+    progress.finish_and_clear();
+    // the monitor already said why, the results of a degraded server mean nothing
+    if cancel.is_cancelled() {
+        out.flush()?;
+        std::process::exit(1);
+    }
+
+    // warmup is excluded, so the pass takes as long as its slowest thread's timed part
+    let duration = total.duration;
+    if args.output_format == OutputFormat::Hdr {
+        hdr_intervals.push(hdr::Interval {
+            tag: match args.compare_protocols {
+                true => format!("{:?}-{}", args.protocol, operation.name()).to_lowercase(),
+                false => operation.name(),
+            },
+            start: pass_start.duration_since(*log_start).unwrap_or_default(),
+            duration,
+            histogram: hdr::histogram(total.latencies.samples())?,
+        });
+    }
+    if args.output_format == OutputFormat::Table {
+        writeln!(
+            out,
+            "{}",
+            table::pass_table(&operation.name(), &threads, &total, palette)
+        )?;
+    }
+    if args.output_format == OutputFormat::Text {
+        for thread in threads.iter() {
+            thread.print(out, operation, args.validate_sequence)?;
+        }
+
+        let label = format!("aggregate {}", operation.name());
+        writeln!(
+            out,
+            "{}: {} ops in {:?}, throughput: {:.2} ops/sec, errors: {} ({:.2}%), retries: {}, permanent errors: {}",
+            label,
+            total.latencies.len(),
+            duration,
+            total.latencies.len() as f64 / duration.as_secs_f64(),
+            total.errors,
+            total.error_rate() * 100.0,
+            total.retries,
+            total.permanent_errors,
+        )?;
+        total.latencies.print(out, &label)?;
+        total.print_responses(out, &label)?;
+        writeln!(
+            out,
+            "{} packet loss: {:.2}% ({} timeouts, {} socket errors)",
+            label,
+            total.packet_loss.loss_rate(total.completed()) * 100.0,
+            total.packet_loss.timeouts(),
+            total.packet_loss.socket_errors()
+        )?;
+        writeln!(
+            out,
+            "{} bandwidth: {:.2} MB/s ({} bytes sent, {} bytes received)",
+            label,
+            pass.bytes.mb_per_sec(duration),
+            pass.bytes.sent(),
+            pass.bytes.received()
+        )?;
+        writeln!(
+            out,
+            "{} peak channel occupancy: {} of {}",
+            label, total.peak_channel_occupancy, args.channel_capacity
+        )?;
+        if let Some(hit_rate) = pass.hits.hit_rate() {
+            writeln!(
+                out,
+                "{} hit rate: {:.2}% ({} hits, {} misses)",
+                label,
+                hit_rate * 100.0,
+                pass.hits.hits(),
+                pass.hits.misses()
+            )?;
+        }
+        if args.validate_sequence {
+            total.sequence.print(out, &label)?;
+        }
+    }
+
+    let consistency = match (args.consistency_check, operation) {
+        (true, Operation::Get) => {
+            let mut consistency = ConsistencySummary::default();
+            for addr in addrs.iter() {
+                consistency
+                    .merge(&consistency_check(args, addr, test_dict, tls.as_ref(), shared).await?);
+            }
+            Some(consistency)
+        }
+        _ => None,
+    };
+    // the counters replaced the dataset values, put them back for the passes after this one.
+    // the same seed draws the same ttls the dataset was stored with
+    if matches!(operation, Operation::Incr | Operation::Decr) {
+        for server in servers.iter() {
+            let (server, test_dict, args, seed) =
+                (server.clone(), test_dict.clone(), Arc::clone(args), *seed);
+            tokio::task::spawn_blocking(move || {
+                store_memcached_values(
+                    &server,
+                    &test_dict,
+                    &mut StdRng::seed_from_u64(seed),
+                    args.ttl,
+                    args.ttl_jitter,
+                    args.set_flags,
+                )
+            })
+            .await??;
+        }
+    }
+    if let (Some(consistency), true) = (&consistency, args.output_format.is_human()) {
+        writeln!(
+            out,
+            "consistency check: {} verified, {} mismatches, {} missing, {} errors",
+            consistency.verified, consistency.mismatches, consistency.missing, consistency.errors
+        )?;
+    }
+
+    Ok(PassReport {
+        duration,
+        total,
+        hits: pass.hits,
+        unmeasured_gets: pass.unmeasured_hits.sent(),
+        bytes: pass.bytes,
+        consistency,
+    })
+}
+
+// the summary of one run in --output-format. `csv_header` for the first row of the output
+fn print_run_summary(
+    out: &mut Writer,
+    args: &Cli,
+    bench: &Benchmark,
+    report: RunReport,
+    run: usize,
+    csv_header: bool,
+) -> Result<(), Box<dyn Error>> {
+    // the text and table summaries show the same rows, as lines or as a table
+    let rows = match args.output_format.is_human() {
+        true => {
+            let summary = report.total.summary(report.duration);
+            let p = &bench.palette;
+            let mut rows = vec![
+                (
+                    "time elapsed".to_string(),
+                    p.plain(format!("{:?}", report.duration)),
+                ),
+                ("ops".to_string(), p.plain(report.total.latencies.len())),
+                (
+                    "ops/sec".to_string(),
+                    p.throughput(format!("{:.2}", summary.ops_per_sec)),
+                ),
+                (
+                    "errors".to_string(),
+                    p.errors(
+                        summary.error_count as u64,
+                        format!(
+                            "{} ({:.2}%)",
+                            summary.error_count as u64,
+                            summary.error_rate * 100.0
+                        ),
+                    ),
+                ),
+                ("retries".to_string(), p.plain(summary.retry_count)),
+                (
+                    "packet loss".to_string(),
+                    p.plain(format!(
+                        "{:.2}% ({} timeouts)",
+                        summary.packet_loss_rate * 100.0,
+                        summary.timeout_count
+                    )),
+                ),
+                (
+                    "bandwidth".to_string(),
+                    p.plain(format!(
+                        "{:.2} MB/s ({} bytes sent, {} bytes received)",
+                        report.bytes.mb_per_sec(report.duration),
+                        report.bytes.sent(),
+                        report.bytes.received()
+                    )),
+                ),
+            ];
+            for (kind, count) in report.shared.error_kinds() {
+                rows.push((format!("errors: {}", kind.name()), p.errors(count, count)));
+            }
+            if let (Some(min), Some(max)) =
+                (report.shared.min_latency(), report.shared.max_latency())
+            {
+                rows.push(("min latency".to_string(), p.latency(min)));
+                rows.push(("max latency".to_string(), p.latency(max)));
+            }
+            if let Some(hit_rate) = report.hits.hit_rate() {
+                rows.push((
+                    "hit rate".to_string(),
+                    p.plain(format!("{:.2}%", hit_rate * 100.0)),
+                ));
+            }
+            for stats in report.server_stats.iter() {
+                for (field, delta) in stats.deltas.iter() {
+                    rows.push((
+                        format!("{} {}", stats.server, field),
+                        p.plain(format!("{:+}", delta)),
+                    ));
+                }
+                rows.push((
+                    format!("{} evicted items", stats.server),
+                    p.plain(stats.evicted_items),
+                ));
+                rows.push((
+                    format!("{} expired items", stats.server),
+                    p.plain(stats.expired_items),
+                ));
+            }
+            for delta in report.memory.iter() {
+                rows.push((
+                    format!("{} memory added", delta.server),
+                    p.plain(format!(
+                        "{} bytes ({:.2} MB)",
+                        delta.memory_added_bytes, delta.memory_added_mb
+                    )),
+                ));
+            }
+            for eviction in report.evictions.iter().flatten() {
+                rows.push((
+                    format!("{} evictions", eviction.server),
+                    p.plain(eviction.evictions),
+                ));
+                rows.push((
+                    format!("{} evictions/sec", eviction.server),
+                    p.plain(format!("{:.2}", eviction.evictions_per_sec)),
+                ));
+                rows.push((
+                    format!("{} evictions per set", eviction.server),
+                    p.plain(format!("{:.4}%", eviction.evictions_per_set * 100.0)),
+                ));
+            }
+            rows
+        }
+        false => vec![],
+    };
+
+    match args.output_format {
+        OutputFormat::Text => {
+            // stats
+            for stats in report.snapshots.iter() {
+                writeln!(out, "stats: {:?}", stats)?;
+            }
+            for (name, value) in rows {
+                writeln!(out, "{}: {}", name, value.content())?;
+            }
+        }
+        OutputFormat::Table => {
+            writeln!(out, "{}", table::summary_table(rows, &bench.palette))?;
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let report = Report {
+                timestamp_ms: (args.output_format == OutputFormat::Ndjson).then(unix_time_ms),
+                seed: bench.seed,
+                summary: Summary {
+                    hit_rate: report.hits.hit_rate(),
+                    ..report
+                        .total
+                        .summary(report.duration)
+                        .with_bytes(&report.bytes, report.duration)
+                },
+                operations: report.operations,
+                error_kinds: report
+                    .shared
+                    .error_kinds()
+                    .into_iter()
+                    .map(|(kind, count)| (kind.name(), count))
+                    .collect(),
+                server_stats: report.server_stats,
+                memory: report.memory,
+                evictions: report.evictions,
+            };
+            writeln!(out, "{}", serde_json::to_string(&report)?)?;
+        }
+        OutputFormat::Csv => {
+            // a sweep appending to one file keeps the header of its first invocation
+            if csv_header {
+                writeln!(out, "{}", CSV_HEADER)?;
+            }
+            writeln!(
+                out,
+                "{}",
+                report.total.summary(report.duration).csv_row(run)
+            )?;
+        }
+        // the histograms of every run go out together once the last one is done
+        OutputFormat::Hdr => {}
+    }
+    Ok(())
+}
+
+// --runs: the mean and spread of the runs of one protocol
+fn print_runs_summary(
+    out: &mut Writer,
+    args: &Cli,
+    palette: &table::Palette,
+    runs: RunsSummary,
+) -> Result<(), Box<dyn Error>> {
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(
+                out,
+                "across {} runs: {:.2} ± {:.2} ops/sec, p99 {:.3}µs ± {:.3}µs, error rate {:.2}% ± {:.2}%",
+                runs.runs,
+                runs.ops_per_sec.mean,
+                runs.ops_per_sec.stddev,
+                runs.p99_us.mean,
+                runs.p99_us.stddev,
+                runs.error_rate.mean * 100.0,
+                runs.error_rate.stddev * 100.0
+            )?;
+        }
+        OutputFormat::Table => {
+            let p = palette;
+            let rows = vec![
+                ("runs".to_string(), p.plain(runs.runs)),
+                (
+                    "ops/sec".to_string(),
+                    p.throughput(format!(
+                        "{:.2} ± {:.2}",
+                        runs.ops_per_sec.mean, runs.ops_per_sec.stddev
+                    )),
+                ),
+                (
+                    "p99".to_string(),
+                    p.plain(format!(
+                        "{:.3}µs ± {:.3}µs",
+                        runs.p99_us.mean, runs.p99_us.stddev
+                    )),
+                ),
+                (
+                    "error rate".to_string(),
+                    p.plain(format!(
+                        "{:.2}% ± {:.2}%",
+                        runs.error_rate.mean * 100.0,
+                        runs.error_rate.stddev * 100.0
+                    )),
+                ),
+            ];
+            writeln!(out, "{}", table::summary_table(rows, p))?;
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let runs = RunsSummary {
+                timestamp_ms: (args.output_format == OutputFormat::Ndjson).then(unix_time_ms),
+                ..runs
+            };
+            writeln!(out, "{}", serde_json::to_string(&runs)?)?;
+        }
+        // one row per run already, the spread is left to the consumer
+        OutputFormat::Csv | OutputFormat::Hdr => {}
+    }
+    Ok(())
+}
+
+// --compare-protocols: the mean of the runs of each protocol side by side
+fn print_comparison(
+    out: &mut Writer,
+    args: &Cli,
+    palette: &table::Palette,
+    comparison: Vec<(Protocol, RunsSummary)>,
+) -> Result<(), Box<dyn Error>> {
+    let name = |protocol: Protocol| format!("{:?}", protocol).to_lowercase();
+    match args.output_format {
+        OutputFormat::Text => {
+            for (protocol, runs) in comparison.iter() {
+                writeln!(
+                    out,
+                    "{}: {:.2} ops/sec, p99 {:.3}µs, error rate {:.2}%",
+                    name(*protocol),
+                    runs.ops_per_sec.mean,
+                    runs.p99_us.mean,
+                    runs.error_rate.mean * 100.0
+                )?;
+            }
+        }
+        OutputFormat::Table => {
+            let columns: Vec<(String, &RunsSummary)> = comparison
+                .iter()
+                .map(|(protocol, runs)| (name(*protocol), runs))
+                .collect();
+            writeln!(out, "{}", table::comparison_table(&columns, palette))?;
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let report = ProtocolComparison {
+                timestamp_ms: (args.output_format == OutputFormat::Ndjson).then(unix_time_ms),
+                protocols: comparison
+                    .into_iter()
+                    .map(|(protocol, runs)| ProtocolRuns {
+                        protocol: name(protocol),
+                        runs,
+                    })
+                    .collect(),
+            };
+            writeln!(out, "{}", serde_json::to_string(&report)?)?;
+        }
+        // the rows of both protocols follow each other under one header, udp first, and
+        // the histograms are tagged with their protocol
+        OutputFormat::Csv | OutputFormat::Hdr => {}
+    }
+    Ok(())
+}
+
+fn unix_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// a --duration pass has no known length, so it only counts completed requests
+fn new_progress_bar(total_ops: Option<u64>) -> ProgressBar {
+    let (template, progress) = match total_ops {
+        Some(total_ops) => (
+            "{elapsed_precise} [{bar:40}] {pos}/{len} ops, {per_sec}, eta {eta}",
+            ProgressBar::new(total_ops),
+        ),
+        None => (
+            "{elapsed_precise} {pos} ops, {per_sec}",
+            ProgressBar::no_length(),
+        ),
+    };
+    let style = ProgressStyle::with_template(template)
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ");
+    progress.with_style(style)
+}
+
+// --server-address given as an IPv6 literal, e.g. ::1
+fn is_ipv6(host: &str) -> bool {
+    host.parse::<Ipv6Addr>().is_ok()
+}
+
+// host:port, with IPv6 literals in brackets ([::1]:11211)
+fn server_addr(host: &str, port: &str) -> String {
+    match is_ipv6(host) {
+        true => format!("[{}]:{}", host, port),
+        false => format!("{}:{}", host, port),
+    }
+}
+
+// `addr` is host:port, or the socket path with -l unix
+fn get_server(args: &Cli, addr: &str) -> Result<memcache::Client, MemcacheError> {
+    match args.protocol {
+        // the memcache crate binds its UDP socket to 0.0.0.0, so an IPv6 server gets its
+        // dataset over TCP. the benchmark itself still runs over UDP
+        Protocol::Udp if addr.starts_with('[') => {
+            memcache::connect(format!("memcache://{}?timeout=10", addr))
+        }
+        Protocol::Udp => memcache::connect(format!("memcache+udp://{}?timeout=10", addr)),
+        Protocol::Tcp => memcache::connect(format!("memcache://{}?timeout=10", addr)),
+        Protocol::Unix => memcache::connect(format!("memcache+unix://{}", addr)),
+        // the memcache crate brings its own (openssl) TLS, configured through the url
+        Protocol::Tls => {
+            let mut url = format!("memcache+tls://{}?timeout=10", addr);
+            for (param, path) in [
+                ("ca_path", &args.tls_ca_cert),
+                ("cert_path", &args.tls_client_cert),
+                ("key_path", &args.tls_client_key),
+            ] {
+                if let Some(path) = path {
+                    url.push_str(&format!("&{}={}", param, path.display()));
+                }
+            }
+            memcache::connect(url)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn Error>> {
+    let matches = Cli::command().get_matches();
+    let mut args = match matches.get_one::<PathBuf>("config") {
+        // the file's options go first, so the command line's own ones are parsed after them
+        Some(path) => {
+            let mut argv: Vec<_> = std::env::args_os().take(1).collect();
+            argv.extend(config::config_args(path, &matches)?);
+            argv.extend(std::env::args_os().skip(1));
+            Cli::parse_from(argv)
+        }
+        None => Cli::from_arg_matches(&matches)?,
+    };
+    // diagnostics go to stderr, RUST_LOG=debug adds a line per reply
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .init();
+    let seed = *args.seed.get_or_insert_with(rand::random);
+    // validation and the udp size check go by --value-size
+    if let Some(value) = &args.fixed_value {
+        args.value_size = value.len();
+        (args.value_size_min, args.value_size_max) = (None, None);
+        args.value_size_distribution = SizeDistribution::Uniform;
+    }
+    // the memcache url takes the path of the socket, so it has to be absolute
+    if let Some(path) = args.unix_socket.as_mut() {
+        *path = std::path::absolute(&*path)?;
+        args.protocol = Protocol::Unix;
+    }
+    if args.protocol == Protocol::Unix && args.unix_socket.is_none() {
+        return Err("-l unix needs the --unix-socket path".into());
+    }
+    if args.tls {
+        args.protocol = Protocol::Tls;
+    }
+    if args.compare_protocols && matches!(args.protocol, Protocol::Unix | Protocol::Tls) {
+        return Err(
+            "--compare-protocols runs udp and tcp, it does not combine with -l unix or -l tls"
+                .into(),
+        );
+    }
+    let tls = match args.protocol {
+        Protocol::Tls => Some(tls::client_config(
+            args.tls_ca_cert.as_deref(),
+            args.tls_client_cert.as_deref(),
+            args.tls_client_key.as_deref(),
+        )?),
+        _ => None,
+    };
+    for (name, sizes) in [("key", args.key_sizes()), ("value", args.value_sizes())] {
+        if sizes.is_empty() {
+            return Err(format!(
+                "--{}-size-min {} is larger than --{}-size-max {}",
+                name,
+                sizes.start(),
+                name,
+                sizes.end()
+            )
+            .into());
+        }
+    }
+    // the records bring their own ports, so they only stand in for the hosts in args
+    let srv_servers = match &args.srv_record {
+        Some(_) if args.protocol == Protocol::Unix => {
+            return Err("--srv-record does not combine with -l unix".into());
+        }
+        Some(name) => {
+            let transport = match args.protocol {
+                Protocol::Udp => "udp",
+                _ => "tcp",
+            };
+            let servers = srv::resolve(name, transport, seed).await?;
+            args.server_address = servers.iter().map(|server| server.host.clone()).collect();
+            Some(servers)
+        }
+        None => None,
+    };
+    let args = Arc::new(args);
+    let mut out = Writer::new(args.output_file.as_deref(), args.append)?;
+    // colors only for a terminal, and never with NO_COLOR set (https://no-color.org)
+    let palette = table::Palette {
+        enabled: args.output_file.is_none()
+            && std::io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        latency_warn: Duration::from_secs_f64(args.latency_warn_ms / 1000.0),
+        latency_critical: Duration::from_secs_f64(args.latency_critical_ms / 1000.0),
+    };
+
+    if args.pipeline_depth > 1 && args.protocol == Protocol::Udp {
+        tracing::warn!(
+            "--pipeline-depth only applies to tcp and unix sockets, sending one request at a time"
+        );
+    }
+
+    let (key_sizes, value_sizes) = (
+        args.key_size_distribution()?,
+        args.value_size_distribution()?,
+    );
+    for (name, distribution, mean, stddev) in [
+        (
+            "key",
+            args.key_size_distribution,
+            args.key_size_mean,
+            args.key_size_stddev,
+        ),
+        (
+            "value",
+            args.value_size_distribution,
+            args.value_size_mean,
+            args.value_size_stddev,
+        ),
+    ] {
+        if distribution == SizeDistribution::Uniform && (mean.is_some() || stddev.is_some()) {
+            tracing::warn!(
+                "--{}-size-mean and --{}-size-stddev need a normal or lognormal --{}-size-distribution",
+                name, name, name
+            );
+        }
+    }
+    if args.key_prefix.len() + key_sizes.sizes().end() > MAX_KEY_LENGTH {
+        tracing::warn!(
+            "--key-prefix of {} bytes plus a key size of {} exceeds the {} byte memcached key limit",
+            args.key_prefix.len(),
+            key_sizes.sizes().end(),
+            MAX_KEY_LENGTH
+        );
+    }
+
+    if args.key_format == KeyFormat::Numeric
+        && NUM_ENTRIES.to_string().len() > *key_sizes.sizes().start()
+    {
+        tracing::warn!(
+            "a key size of {} is too short for {} numeric keys, keys will be longer",
+            key_sizes.sizes().start(),
+            NUM_ENTRIES
+        );
+    }
+
+    if args.integrity_hash.is_some() && !args.validate {
+        tracing::warn!("--integrity-hash is only checked with --validate");
+    }
+
+    if args.warmup_threads.is_some() && args.warmup_ops == 0 {
+        tracing::warn!("--warmup-threads needs --warmup-ops, there is no warmup");
+    }
+
+    if args.miss_penalty_ms > 0 && args.pipeline_depth > 1 {
+        tracing::warn!("--miss-penalty-ms does not apply with --pipeline-depth");
+    }
+
+    if args.closed_loop && args.pipeline_depth > 1 {
+        tracing::warn!("--pipeline-depth does not apply with --closed-loop");
+    }
+
+    if args.validate_sequence && args.protocol != Protocol::Udp {
+        tracing::warn!("--validate-sequence only applies to udp");
+    }
+
+    if args.tcp_keepalive_s.is_some() && matches!(args.protocol, Protocol::Udp | Protocol::Unix) {
+        tracing::warn!("--tcp-keepalive-s only applies to tcp and tls");
+    }
+
+    if args.udp_sockets > 1 && args.protocol != Protocol::Udp {
+        tracing::warn!("--udp-sockets only applies to udp");
+    }
+
+    if args.ramp_duration_s > 0 && args.rate == 0 {
+        tracing::warn!("--ramp-duration-s needs a --rate to ramp up to, the ramp is ignored");
+    }
+
+    if (args.hot_key_fraction > 0.0) != (args.hot_key_ratio > 0.0) {
+        tracing::warn!("--hot-key-fraction and --hot-key-ratio only take effect together");
+    }
+
+    // what the kernel made of the requested buffer sizes
+    if args.protocol == Protocol::Udp {
+        let socket = bind_udp_socket(&args, is_ipv6(&args.server_address[0]))?;
+        tracing::info!(
+            send_buffer_bytes = socket.send_buffer_size()?,
+            recv_buffer_bytes = socket.recv_buffer_size()?,
+            "udp socket buffers"
+        );
+    }
+
+    // assign server address, a unix socket stands in for every --server-address
+    let addrs: Arc<Vec<String>> = Arc::new(match (&args.unix_socket, &srv_servers) {
+        (Some(path), _) => vec![path.display().to_string()],
+        // a TLS server is verified against the name of its target, not the address
+        (None, Some(servers)) => servers
+            .iter()
+            .map(|server| match args.protocol {
+                Protocol::Tls => server_addr(&server.target, &server.port.to_string()),
+                _ => server_addr(&server.host, &server.port.to_string()),
+            })
+            .collect(),
+        (None, None) => args
+            .server_address
+            .iter()
+            .map(|addr| server_addr(addr, &args.port))
+            .collect(),
+    });
+
+    if args.output_format.is_human() {
+        writeln!(
+            out,
+            "memcached benchmark: server {} over {:?}, {} threads x {} ops, seed {}",
+            addrs.join(", "),
+            args.protocol,
+            args.threads,
+            args.nums,
+            seed
+        )?;
+    }
+
+    let mut servers = vec![];
+    for addr in addrs.iter() {
+        let unreachable =
+            |e: MemcacheError| format!("memcached server {} is unreachable: {}", addr, e);
+        // the memcache crate is synchronous, keep its connect off the runtime threads. its
+        // duration covers the handshake, and for tls certificate verification as well
+        let connect_start = Instant::now();
+        let server = {
+            let (addr, args) = (addr.clone(), Arc::clone(&args));
+            tokio::task::spawn_blocking(move || get_server(&args, &addr)).await?
+        }
+        .map_err(unreachable)?;
+        let connect_duration_ms = connect_start.elapsed().as_secs_f64() * 1000.0;
+        let version = {
+            let server = server.clone();
+            tokio::task::spawn_blocking(move || health_check(&server)).await?
+        }
+        .map_err(unreachable)?;
+        tracing::info!(
+            server = %addr,
+            version = %version,
+            connect_duration_ms = format_args!("{:.3}", connect_duration_ms),
+            "memcached server"
+        );
+        // a cas round trip starts with a gets
+        let mut required_ops = args.operation.clone();
+        if required_ops.contains(&Operation::Cas) {
+            required_ops.push(Operation::Gets);
+        }
+        check_server_compatibility(&version, &required_ops, args.protocol)
+            .map_err(|e| format!("memcached server {}: {}", addr, e))?;
+        servers.push(server);
+    }
+    for server in servers.iter() {
+        let server = server.clone();
+        tokio::task::spawn_blocking(move || exmaple_method(&server)).await??;
+    }
+
+    if args.key_boundary_test {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut results = vec![];
+        for addr in addrs.iter() {
+            results.extend(key_boundary_test(&args, addr, tls.as_ref(), &mut rng).await?);
+        }
+        match args.output_format.is_human() {
+            true => {
+                for result in results.iter() {
+                    let outcome = match (result.stored, result.read_back) {
+                        (true, true) => "stored and read back".to_string(),
+                        (true, false) => "stored, but not read back under the same key".to_string(),
+                        (false, _) => format!("not stored: {}", result.reply),
+                    };
+                    writeln!(
+                        out,
+                        "{} key length {}: {}",
+                        result.server, result.key_length, outcome
+                    )?;
+                }
+            }
+            false => writeln!(out, "{}", serde_json::to_string(&results)?)?,
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let mut test_dict = match &args.dataset_file {
+        Some(path) => load_memcached_test_dict(path)?,
+        None => generate_memcached_test_dict(
+            &mut StdRng::seed_from_u64(seed),
+            &args,
+            &key_sizes,
+            &value_sizes,
+            new_value_generator(args.value_pattern, args.fixed_value.as_deref()).as_mut(),
+            NUM_ENTRIES,
+        ),
+    };
+    if test_dict.is_empty() {
+        return Err("the dataset is empty".into());
+    }
+    if let Some(hash) = args.integrity_hash {
+        for value in test_dict.values_mut() {
+            hash.append(value);
+        }
+    }
+    // a multi-datagram reply is lost with any one of its datagrams, so large values need tcp.
+    // checked against the generated values, the bounds of a normal or lognormal size
+    // distribution are far beyond the sizes it draws
+    if args.protocol == Protocol::Udp {
+        let oversized: Vec<usize> = test_dict
+            .iter()
+            .filter(|(key, value)| {
+                get_reply_len(key.len(), value.len()) > UDP_MAX_DATAGRAM - UDP_HEADER_SIZE
+            })
+            .map(|(_, value)| value.len())
+            .collect();
+        if let Some(largest_value) = oversized.iter().max() {
+            tracing::warn!(
+                "{} of the {} values, up to {} bytes, do not fit in one {} byte UDP datagram, a reply losing any of its datagrams times out; use -l tcp for large values",
+                oversized.len(),
+                test_dict.len(),
+                largest_value,
+                UDP_MAX_DATAGRAM
+            );
+        }
+    }
+    if let Some(size) = args.working_set_size {
+        if size as usize > test_dict.len() {
+            return Err(format!(
+                "--working-set-size {} exceeds the {} keys of the dataset",
+                size,
+                test_dict.len()
+            )
+            .into());
+        }
+        if args.trace_file.is_some() {
+            tracing::warn!(
+                "--working-set-size does not apply to --trace-file, replaying the trace"
+            );
+        }
+    }
+
+    let trace = match &args.trace_file {
+        Some(path) => Some(Arc::new(load_trace(path, &test_dict)?)),
+        None => None,
+    };
+    let test_dict = Arc::new(test_dict);
+
+    // the memory the dataset and the benchmark add is measured from before the dataset
+    let population_snapshots = take_stats_snapshots(&servers).await?;
+
+    // assign test_dict to every server, each one draws the same ttls. storing a large
+    // dataset through the synchronous client blocks, so it runs on the blocking pool
+    for server in servers.iter() {
+        let (server, test_dict, args) = (server.clone(), test_dict.clone(), Arc::clone(&args));
+        tokio::task::spawn_blocking(move || {
+            set_memcached_value(
+                &server,
+                test_dict,
+                &mut StdRng::seed_from_u64(seed),
+                args.ttl,
+                args.ttl_jitter,
+                args.set_flags,
+            )
+        })
+        .await??;
+    }
+
+    let metrics = match args.metrics_port {
+        Some(port) => {
+            let metrics = metrics::Metrics::new()?;
+            let server = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(server, port).await {
+                    tracing::error!(port, error = %e, "metrics endpoint failed");
+                }
+            });
+            Some(metrics)
+        }
+        None => None,
+    };
+    // --compare-protocols: every run over udp, then over tcp, against the same dataset
+    let protocols = match args.compare_protocols {
+        true => vec![Protocol::Udp, Protocol::Tcp],
+        false => vec![args.protocol],
+    };
+    let keepalive_pinger = args
+        .tcp_keepalive_s
+        .filter(|_| matches!(args.protocol, Protocol::Tcp | Protocol::Tls))
+        .map(|secs| {
+            tokio::spawn(keepalive_pinger(
+                addrs.iter().cloned().zip(servers.iter().cloned()).collect(),
+                Duration::from_secs(secs),
+            ))
+        });
+    let stats_poller = (args.stats_interval_s > 0).then(|| {
+        tokio::spawn(stats_poller(
+            addrs.iter().cloned().zip(servers.iter().cloned()).collect(),
+            Duration::from_secs(args.stats_interval_s),
+        ))
+    });
+    let schedule = Arc::new(match &srv_servers {
+        Some(servers) => srv::schedule(servers),
+        None => (0..addrs.len()).collect(),
+    });
+    let bench = Benchmark {
+        addrs,
+        schedule,
+        servers,
+        test_dict,
+        trace,
+        tls,
+        metrics,
+        cas_tokens: CasTokenStore::default(),
+        cancel: CancellationToken::new(),
+        palette,
+        seed,
+        population_snapshots,
+        log_start: std::time::SystemTime::now(),
+    };
+    let mut all_results = vec![];
+    let mut comparison = vec![];
+    let mut hdr_intervals = vec![];
+    for &protocol in protocols.iter() {
+        let args = Arc::new(Cli {
+            protocol,
+            ..Cli::clone(&args)
+        });
+        if args.compare_protocols && args.output_format.is_human() {
+            writeln!(out, "protocol {:?}", protocol)?;
+        }
+        let mut results = vec![];
+
+        for run in 1..=args.runs as usize {
+            if args.runs > 1 && args.output_format.is_human() {
+                writeln!(out, "run {} of {}", run, args.runs)?;
+            }
+
+            let report = execute_run(&bench, &args, &mut out, &mut hdr_intervals).await?;
+            results.push(RunResult::from(&report.total.summary(report.duration)));
+            // a sweep appending to one file keeps the header of its first invocation
+            let csv_header = run == 1 && protocol == protocols[0] && out.started_empty();
+            print_run_summary(&mut out, &args, &bench, report, run, csv_header)?;
+        }
+
+        if args.runs > 1 {
+            print_runs_summary(&mut out, &args, &palette, RunsSummary::new(&results))?;
+        }
+
+        comparison.push((protocol, RunsSummary::new(&results)));
+        all_results.extend(results);
+    }
+
+    if args.compare_protocols {
+        print_comparison(&mut out, &args, &palette, comparison)?;
+    }
+    if let Some(stats_poller) = stats_poller {
+        stats_poller.abort();
+    }
+    if let Some(keepalive_pinger) = keepalive_pinger {
+        keepalive_pinger.abort();
+    }
+
+    if args.output_format == OutputFormat::Hdr {
+        hdr::write_log(&mut out, bench.log_start, &hdr_intervals)?;
+    }
+
+    // with --runs, the mean error rate of the runs, of both protocols with --compare-protocols
+    if let Some(threshold) = args.fail_on_error_rate {
+        let error_rate = RunsSummary::new(&all_results).error_rate.mean;
+        if error_rate > threshold {
+            tracing::error!(
+                "error rate {:.2}% exceeds --fail-on-error-rate {:.2}%",
+                error_rate * 100.0,
+                threshold * 100.0
+            );
+            out.flush()?;
+            std::process::exit(1);
+        }
+    }
+
+    // latency SLAs, checked against the mean over the runs just like the error rate
+    let mean_ms = |latency_us: fn(&RunResult) -> f64| {
+        all_results.iter().map(latency_us).sum::<f64>() / all_results.len() as f64 / 1000.0
+    };
+    let sla_checks = [
+        (
+            "p99",
+            "--sla-p99-ms",
+            args.sla_p99_ms,
+            2,
+            mean_ms(|r| r.p99_us),
+        ),
+        (
+            "p99.9",
+            "--sla-p999-ms",
+            args.sla_p999_ms,
+            3,
+            mean_ms(|r| r.p999_us),
+        ),
+    ];
+    for (name, flag, threshold_ms, code, latency_ms) in sla_checks {
+        if let Some(threshold_ms) = threshold_ms.filter(|&threshold_ms| latency_ms > threshold_ms) {
+            tracing::error!(
+                "{} latency {:.3}ms exceeds {} {:.3}ms",
+                name,
+                latency_ms,
+                flag,
+                threshold_ms
+            );
+            out.flush()?;
+            std::process::exit(code);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(protocol: &str) -> Cli {
+        Cli::parse_from(["memcached_benchmark", "-l", protocol])
+    }
+
+    #[test]
+    fn server_addr_brackets_ipv6_literals() {
+        assert_eq!(server_addr("::1", "11211"), "[::1]:11211");
+        assert_eq!(server_addr("127.0.0.1", "11211"), "127.0.0.1:11211");
+        assert_eq!(server_addr("localhost", "11211"), "localhost:11211");
+    }
+
+    #[test]
+    fn checks_the_server_version() {
+        let check = |version, ops: &[Operation], protocol| {
+            check_server_compatibility(version, ops, protocol).is_ok()
+        };
+        assert!(check("1.6.21", &[Operation::Cas], Protocol::Tcp));
+        assert!(check("1.3.0", &[Operation::Gets], Protocol::Udp));
+        // the dataset goes over the binary protocol with -l udp as well
+        assert!(!check("1.2.8", &[Operation::Get], Protocol::Tcp));
+        assert!(!check("1.2.8", &[Operation::Get], Protocol::Udp));
+        // e.g. a proxy
+        assert!(check("twemproxy", &[Operation::Cas], Protocol::Tcp));
+    }
+
+    #[tokio::test]
+    async fn connects_over_ipv6() {
+        let listener = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).unwrap();
+        let addr = server_addr("::1", &listener.local_addr().unwrap().port().to_string());
+
+        let tcp = Connection::connect(&cli("tcp"), &addr, None).await.unwrap();
+        assert!(matches!(tcp, Connection::Tcp(..)));
+
+        let Connection::Udp(sockets, ..) =
+            Connection::connect(&cli("udp"), &addr, None).await.unwrap()
+        else {
+            panic!("-l udp has to connect over udp");
+        };
+        assert!(sockets[0].local_addr().unwrap().is_ipv6());
+
+        // the dataset of an IPv6 server goes over tcp, with -l udp as well
+        for protocol in ["tcp", "udp"] {
+            let (args, addr) = (cli(protocol), addr.clone());
+            let server = tokio::task::spawn_blocking(move || get_server(&args, &addr)).await;
+            assert!(server.unwrap().is_ok(), "-l {}", protocol);
+        }
+    }
+
+    // kills the memcached of a test when it ends, passing or not
+    struct ServerProcess(std::process::Child);
+
+    impl Drop for ServerProcess {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    // skipped when there is no memcached on the PATH
+    #[tokio::test]
+    async fn sets_and_gets_over_ipv6() {
+        let port = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+            .to_string();
+        let spawned = std::process::Command::new("memcached")
+            .args(["-l", "::1", "-p", &port, "-U", &port, "-u", "nobody"])
+            .spawn();
+        let _server = match spawned {
+            Ok(child) => ServerProcess(child),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("no memcached on the PATH, skipping");
+                return;
+            }
+            Err(e) => panic!("memcached did not start: {}", e),
+        };
+        let addr = server_addr("::1", &port);
+        for _ in 0..50 {
+            if std::net::TcpStream::connect((Ipv6Addr::LOCALHOST, port.parse().unwrap())).is_ok() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        let test_dict: HashMap<String, Vec<u8>> = (0..10)
+            .map(|i| (format!("key{}", i), format!("value{}", i).into_bytes()))
+            .collect();
+        let test_dict = Arc::new(test_dict);
+        for protocol in ["tcp", "udp"] {
+            let args = cli(protocol);
+            let (server_args, server_addr, dict) = (args.clone(), addr.clone(), test_dict.clone());
+            tokio::task::spawn_blocking(move || {
+                let server = get_server(&server_args, &server_addr)?;
+                set_memcached_value(&server, dict, &mut StdRng::seed_from_u64(0), 0, 0, 0)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+            let mut connection = Connection::connect(&args, &addr, None).await.unwrap();
+            let mut buf = Vec::with_capacity(BUFFER_SIZE);
+            for (seq, (key, value)) in test_dict.iter().enumerate() {
+                let seq = seq as u16;
+                connection
+                    .send(&wrap_get_command(key, seq), &addr)
+                    .await
+                    .unwrap();
+                let amt = recv_reply(
+                    &mut connection,
+                    Operation::Get,
+                    &mut buf,
+                    seq,
+                    Duration::from_secs(1),
+                    None,
+                )
+                .await
+                .unwrap()
+                .unwrap();
+                let blocks = parse_value_blocks(&buf[..amt]);
+                assert_eq!(
+                    blocks.first().map(|(_, data)| *data),
+                    Some(value.as_slice())
+                );
+            }
+        }
+    }
+}
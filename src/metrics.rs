@@ -0,0 +1,131 @@
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// --metrics-port: run-wide counters in the prometheus exposition format. the handles
+// share their values, so every socket task records into a clone
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    ops: IntCounter,
+    errors: IntCounter,
+    latency: Histogram,
+    bytes: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Metrics, prometheus::Error> {
+        let registry = Registry::new();
+        let ops = IntCounter::new(
+            "memcached_benchmark_ops_total",
+            "requests answered by the server",
+        )?;
+        let errors = IntCounter::new(
+            "memcached_benchmark_errors_total",
+            "requests that failed, timed out or got an error reply",
+        )?;
+        // 10µs up to about 0.3s, doubling
+        let latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "memcached_benchmark_latency_seconds",
+                "round trip time of answered requests",
+            )
+            .buckets(exponential_buckets(0.00001, 2.0, 16)?),
+        )?;
+        let bytes = IntCounterVec::new(
+            Opts::new(
+                "memcached_benchmark_throughput_bytes_total",
+                "memcached protocol bytes, without the UDP frame header",
+            ),
+            &["direction"],
+        )?;
+        registry.register(Box::new(ops.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        registry.register(Box::new(latency.clone()))?;
+        registry.register(Box::new(bytes.clone()))?;
+        Ok(Metrics {
+            registry,
+            ops,
+            errors,
+            latency,
+            bytes,
+        })
+    }
+
+    pub fn record_reply(&self, latency: Duration) {
+        self.ops.inc();
+        self.latency.observe(latency.as_secs_f64());
+    }
+
+    pub fn record_error(&self) {
+        self.errors.inc();
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.bytes.with_label_values(&["sent"]).inc_by(bytes as u64);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes
+            .with_label_values(&["received"])
+            .inc_by(bytes as u64);
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, prometheus::Error> {
+        let mut body = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut body)?;
+        Ok(body)
+    }
+}
+
+// answers GET /metrics on `port` until the process exits, anything else is a 404
+pub async fn serve(metrics: Metrics, port: u16) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &metrics).await {
+                tracing::warn!(error = %e, "metrics request failed");
+            }
+        });
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    metrics: &Metrics,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the request line is all that matters, headers and body are ignored
+    let mut request = [0; 1024];
+    let amt = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..amt]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, content_type, body) = match path {
+        "/metrics" => (
+            "200 OK",
+            TextEncoder::new().format_type().to_string(),
+            metrics.encode()?,
+        ),
+        _ => (
+            "404 Not Found",
+            "text/plain".to_string(),
+            b"not found\n".to_vec(),
+        ),
+    };
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    Ok(())
+}
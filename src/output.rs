@@ -0,0 +1,291 @@
+use crate::stats::{ByteCounter, Percentiles};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Serialize)]
+pub struct LatencySummary {
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub p999_us: f64,
+    pub min_us: f64,
+    pub max_us: f64,
+    pub mean_us: f64,
+    pub stddev_us: f64,
+}
+
+impl From<&Percentiles> for LatencySummary {
+    fn from(p: &Percentiles) -> Self {
+        let us = |ns: u64| ns as f64 / 1000.0;
+        LatencySummary {
+            p50_us: us(p.p50),
+            p95_us: us(p.p95),
+            p99_us: us(p.p99),
+            p999_us: us(p.p999),
+            min_us: us(p.min),
+            max_us: us(p.max),
+            mean_us: us(p.mean),
+            stddev_us: us(p.stddev),
+        }
+    }
+}
+
+pub const CSV_HEADER: &str = "run,duration_ms,ops_sec,p50_us,p95_us,p99_us,errors";
+
+#[derive(Serialize)]
+pub struct Summary {
+    pub total_duration_ms: f64,
+    pub ops_per_sec: f64,
+    pub error_count: usize,
+    pub error_rate: f64,
+    pub retry_count: usize,
+    pub permanent_error_count: usize,
+    pub timeout_count: usize,
+    pub socket_error_count: usize,
+    pub packet_loss_rate: f64,
+    pub peak_channel_occupancy: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub mb_per_sec: f64,
+    // only set for passes that issue gets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit_rate: Option<f64>,
+    pub latency: LatencySummary,
+}
+
+impl Summary {
+    pub fn new(duration: Duration, ops: usize, error_count: usize, latency: &Percentiles) -> Self {
+        Summary {
+            total_duration_ms: duration.as_secs_f64() * 1000.0,
+            ops_per_sec: ops as f64 / duration.as_secs_f64(),
+            error_count,
+            error_rate: match ops + error_count {
+                0 => 0.0,
+                requests => error_count as f64 / requests as f64,
+            },
+            retry_count: 0,
+            permanent_error_count: 0,
+            timeout_count: 0,
+            socket_error_count: 0,
+            packet_loss_rate: 0.0,
+            peak_channel_occupancy: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            mb_per_sec: 0.0,
+            hit_rate: None,
+            latency: latency.into(),
+        }
+    }
+
+    pub fn with_bytes(self, bytes: &ByteCounter, duration: Duration) -> Self {
+        Summary {
+            bytes_sent: bytes.sent(),
+            bytes_received: bytes.received(),
+            mb_per_sec: bytes.mb_per_sec(duration),
+            ..self
+        }
+    }
+
+    pub fn csv_row(&self, run: usize) -> String {
+        format!(
+            "{},{:.3},{:.2},{:.3},{:.3},{:.3},{}",
+            run,
+            self.total_duration_ms,
+            self.ops_per_sec,
+            self.latency.p50_us,
+            self.latency.p95_us,
+            self.latency.p99_us,
+            self.error_count
+        )
+    }
+}
+
+// --consistency-check: every key of the dataset read back once after a get pass
+#[derive(Serialize, Default)]
+pub struct ConsistencySummary {
+    pub verified: usize,
+    pub mismatches: usize,
+    pub missing: usize,
+    // keys whose get timed out or failed
+    pub errors: usize,
+}
+
+impl ConsistencySummary {
+    pub fn merge(&mut self, other: &ConsistencySummary) {
+        self.verified += other.verified;
+        self.mismatches += other.mismatches;
+        self.missing += other.missing;
+        self.errors += other.errors;
+    }
+}
+
+#[derive(Serialize)]
+pub struct OperationSummary {
+    pub operation: String,
+    #[serde(flatten)]
+    pub summary: Summary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<ConsistencySummary>,
+}
+
+// the whole run: totals over every pass, followed by each pass on its own
+#[derive(Serialize)]
+pub struct Report {
+    // only in the ndjson summary line, milliseconds since the unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+    pub seed: u64,
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub operations: Vec<OperationSummary>,
+    // failed requests of the run by ErrorKind, validation mismatches got their reply and are
+    // not in error_count
+    pub error_kinds: BTreeMap<&'static str, u64>,
+    pub server_stats: Vec<ServerStatsDelta>,
+    pub memory: Vec<MemoryDelta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evictions: Option<Vec<EvictionSummary>>,
+}
+
+// change of the server counters over the run, one entry per server
+#[derive(Serialize)]
+pub struct ServerStatsDelta {
+    pub server: String,
+    #[serde(flatten)]
+    pub deltas: BTreeMap<&'static str, i64>,
+    // the evictions and expired_unfetched deltas
+    pub evicted_items: i64,
+    pub expired_items: i64,
+}
+
+// change of the bytes stat of one server since before the dataset was stored
+#[derive(Serialize)]
+pub struct MemoryDelta {
+    pub server: String,
+    pub memory_added_bytes: i64,
+    pub memory_added_mb: f64,
+}
+
+// --measure-evictions: what one server evicted over the run
+#[derive(Serialize)]
+pub struct EvictionSummary {
+    pub server: String,
+    pub evictions: i64,
+    pub evictions_per_sec: f64,
+    // over the cmd_set delta of the server, 0 without sets
+    pub evictions_per_set: f64,
+}
+
+// --interval-ms: throughput of the running pass since the previous sample
+#[derive(Serialize)]
+pub struct IntervalSample {
+    // wall clock, milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    // since the pass started
+    pub elapsed_ms: u64,
+    pub operation: String,
+    // replies received so far in the pass
+    pub ops: u64,
+    pub ops_per_sec: f64,
+    // failed requests and mean reply latency within the interval. timeouts point at a slow
+    // server, receive errors at the network or the connection
+    pub errors: u64,
+    pub timeouts: u64,
+    pub recv_errors: u64,
+    pub mean_latency_us: f64,
+}
+
+// --runs: the headline numbers of one whole run
+#[derive(Clone, Copy)]
+pub struct RunResult {
+    pub ops_per_sec: f64,
+    pub p99_us: f64,
+    // only for --sla-p999-ms, the runs summary leaves it out
+    pub p999_us: f64,
+    pub error_rate: f64,
+}
+
+impl From<&Summary> for RunResult {
+    fn from(summary: &Summary) -> Self {
+        RunResult {
+            ops_per_sec: summary.ops_per_sec,
+            p99_us: summary.latency.p99_us,
+            p999_us: summary.latency.p999_us,
+            error_rate: summary.error_rate,
+        }
+    }
+}
+
+// mean and sample standard deviation of one metric over every run, the deviation is 0
+// for a single run
+#[derive(Serialize)]
+pub struct Spread {
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Spread {
+    pub fn of(values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let stddev = match values.len() {
+            0 | 1 => 0.0,
+            _ => (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt(),
+        };
+        Spread { mean, stddev }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RunsSummary {
+    // only in ndjson, milliseconds since the unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+    pub runs: usize,
+    pub ops_per_sec: Spread,
+    pub p99_us: Spread,
+    pub error_rate: Spread,
+}
+
+impl RunsSummary {
+    pub fn new(results: &[RunResult]) -> Self {
+        let spread = |value: fn(&RunResult) -> f64| {
+            Spread::of(&results.iter().map(value).collect::<Vec<_>>())
+        };
+        RunsSummary {
+            timestamp_ms: None,
+            runs: results.len(),
+            ops_per_sec: spread(|r| r.ops_per_sec),
+            p99_us: spread(|r| r.p99_us),
+            error_rate: spread(|r| r.error_rate),
+        }
+    }
+}
+
+// --key-boundary-test: a set and get of one key length on one server
+#[derive(Serialize)]
+pub struct KeyBoundaryResult {
+    pub server: String,
+    pub key_length: usize,
+    // first line of the set reply, e.g. STORED or CLIENT_ERROR bad command line format
+    pub reply: String,
+    pub stored: bool,
+    // the get returned the value under the whole key
+    pub read_back: bool,
+}
+
+// --compare-protocols: the runs of every protocol, in the order they ran
+#[derive(Serialize)]
+pub struct ProtocolComparison {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+    pub protocols: Vec<ProtocolRuns>,
+}
+
+#[derive(Serialize)]
+pub struct ProtocolRuns {
+    pub protocol: String,
+    #[serde(flatten)]
+    pub runs: RunsSummary,
+}
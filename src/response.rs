@@ -0,0 +1,245 @@
+use std::ops::Range;
+
+use crate::UDP_HEADER_SIZE;
+
+// VALUE <key> <flags> <bytes> [<cas unique>], `data` indexes the buffer it was parsed from
+pub struct ValueBlock {
+    pub key: String,
+    pub cas: Option<u64>,
+    pub data: Range<usize>,
+}
+
+// the VALUE blocks at the start of a retrieval response
+pub struct Blocks {
+    pub blocks: Vec<ValueBlock>,
+    // up to and including the END (or error) line, None while more bytes are needed
+    pub len: Option<usize>,
+}
+
+enum State {
+    // at the start of a line, a VALUE header or the line that ends the response
+    Line,
+    // inside the data block announced by the last header
+    Data(ValueBlock),
+}
+
+// the response is read one line or data block at a time, a block cut off by the end of
+// `buf` leaves it incomplete. a line other than a well-formed VALUE header ends it
+pub fn parse_blocks(buf: &[u8]) -> Blocks {
+    let mut blocks = vec![];
+    let mut pos = 0;
+    let mut state = State::Line;
+    loop {
+        state = match state {
+            State::Line => {
+                let Some(i) = buf[pos..].windows(2).position(|w| w == b"\r\n") else {
+                    return Blocks { blocks, len: None };
+                };
+                let header = value_header(&buf[pos..pos + i], pos + i + 2);
+                pos += i + 2;
+                match header {
+                    Some(block) => State::Data(block),
+                    None => {
+                        return Blocks {
+                            blocks,
+                            len: Some(pos),
+                        }
+                    }
+                }
+            }
+            State::Data(block) => {
+                // value_header keeps the end of a block and its line ending from overflowing
+                let end = block.data.end + 2;
+                if end > buf.len() {
+                    return Blocks { blocks, len: None };
+                }
+                pos = end;
+                blocks.push(block);
+                State::Line
+            }
+        };
+    }
+}
+
+// the block a VALUE header line announces, its data starting at `start`. a length that
+// runs past usize makes the header malformed
+fn value_header(line: &[u8], start: usize) -> Option<ValueBlock> {
+    let mut fields = std::str::from_utf8(line)
+        .ok()?
+        .strip_prefix("VALUE ")?
+        .split_whitespace();
+    let key = fields.next()?.to_string();
+    // the flags are not needed, but a header without them is malformed
+    fields.next()?.parse::<u32>().ok()?;
+    let bytes: usize = fields.next()?.parse().ok()?;
+    let cas = fields.next().and_then(|cas| cas.parse().ok());
+    let end = start
+        .checked_add(bytes)
+        .filter(|end| end.checked_add(2).is_some())?;
+    Some(ValueBlock {
+        key,
+        cas,
+        data: start..end,
+    })
+}
+
+// request id, sequence number and datagram count from the frame header of a UDP datagram
+pub fn frame_header(datagram: &[u8]) -> Option<(u16, u16, u16)> {
+    let field = |i: usize| u16::from_be_bytes([datagram[i], datagram[i + 1]]);
+    (datagram.len() >= UDP_HEADER_SIZE).then(|| (field(0), field(2), field(4)))
+}
+
+// a UDP reply put back together from its datagrams
+pub struct ParsedResponse {
+    pub request_id: u16,
+    // the ASCII reply of the datagrams in sequence order, up to the first missing one
+    pub payload: Vec<u8>,
+    // datagrams the frame headers announce that are not among the ones given
+    pub missing_datagrams: usize,
+    // every datagram arrived and `payload` ends with the line that ends the reply
+    pub complete: bool,
+}
+
+// memcached splits a reply larger than a datagram over several, each with the request id, its
+// sequence number and the datagram count in its frame header. they may arrive in any order;
+// datagrams of another request than the first one given are skipped
+pub fn parse_udp_response(bufs: &[&[u8]]) -> ParsedResponse {
+    let mut request_id = None;
+    let mut parts: Vec<Option<&[u8]>> = vec![];
+    for buf in bufs {
+        let Some((id, seq, total)) = frame_header(buf) else {
+            continue;
+        };
+        if *request_id.get_or_insert(id) != id {
+            continue;
+        }
+        // a datagram announcing more than the others grows the reply, so the ones in between
+        // count as missing instead of being dropped
+        if parts.len() < total.max(1) as usize {
+            parts.resize(total.max(1) as usize, None);
+        }
+        if let Some(part) = parts.get_mut(seq as usize) {
+            part.get_or_insert(&buf[UDP_HEADER_SIZE..]);
+        }
+    }
+
+    let missing_datagrams = parts.iter().filter(|part| part.is_none()).count();
+    let payload: Vec<u8> = parts
+        .iter()
+        .map_while(|part| *part)
+        .flatten()
+        .copied()
+        .collect();
+    let complete = missing_datagrams == 0 && parse_blocks(&payload).len == Some(payload.len());
+    ParsedResponse {
+        request_id: request_id.unwrap_or_default(),
+        payload,
+        missing_datagrams,
+        complete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datagram(id: u16, seq: u16, total: u16, payload: &[u8]) -> Vec<u8> {
+        let mut datagram = vec![];
+        for field in [id, seq, total, 0] {
+            datagram.extend_from_slice(&field.to_be_bytes());
+        }
+        datagram.extend_from_slice(payload);
+        datagram
+    }
+
+    #[test]
+    fn parses_value_blocks() {
+        let buf = b"VALUE a 0 3 7\r\nfoo\r\nVALUE b 0 2\r\nhi\r\nEND\r\n";
+        let parsed = parse_blocks(buf);
+        assert_eq!(parsed.len, Some(buf.len()));
+        assert_eq!(parsed.blocks.len(), 2);
+        assert_eq!(parsed.blocks[0].key, "a");
+        assert_eq!(parsed.blocks[0].cas, Some(7));
+        assert_eq!(&buf[parsed.blocks[0].data.clone()], b"foo");
+        // a get reply carries no cas field
+        assert_eq!(parsed.blocks[1].cas, None);
+        assert_eq!(&buf[parsed.blocks[1].data.clone()], b"hi");
+    }
+
+    #[test]
+    fn block_split_across_buffers_is_incomplete() {
+        let buf = b"VALUE a 0 10\r\nfoo";
+        let parsed = parse_blocks(buf);
+        assert_eq!(parsed.len, None);
+        assert!(parsed.blocks.is_empty());
+        // cut off in the middle of the END line
+        assert_eq!(parse_blocks(b"VALUE a 0 3\r\nfoo\r\nEN").len, None);
+    }
+
+    #[test]
+    fn non_value_line_ends_the_response() {
+        let buf = b"SERVER_ERROR out of memory\r\nVALUE a 0 3\r\nfoo\r\n";
+        let parsed = parse_blocks(buf);
+        assert!(parsed.blocks.is_empty());
+        assert_eq!(parsed.len, Some(b"SERVER_ERROR out of memory\r\n".len()));
+        // a header without its flags is not a VALUE header
+        assert_eq!(parse_blocks(b"VALUE a 3\r\nfoo\r\n").blocks.len(), 0);
+    }
+
+    #[test]
+    fn huge_length_is_malformed() {
+        let buf = format!("VALUE a 0 {}\r\nfoo\r\nEND\r\n", usize::MAX - 4);
+        let parsed = parse_blocks(buf.as_bytes());
+        assert!(parsed.blocks.is_empty());
+        assert_eq!(parsed.len, Some(buf.find("\r\n").unwrap() + 2));
+    }
+
+    #[test]
+    fn reassembles_out_of_order_datagrams() {
+        let (first, second) = (
+            datagram(7, 0, 2, b"VALUE a 0 3\r\n"),
+            datagram(7, 1, 2, b"foo\r\nEND\r\n"),
+        );
+        let parsed = parse_udp_response(&[&second, &first]);
+        assert_eq!(parsed.request_id, 7);
+        assert_eq!(parsed.missing_datagrams, 0);
+        assert!(parsed.complete);
+        assert_eq!(parsed.payload, b"VALUE a 0 3\r\nfoo\r\nEND\r\n");
+    }
+
+    #[test]
+    fn missing_middle_datagram() {
+        let parts = [
+            datagram(7, 0, 3, b"VALUE a 0 6\r\n"),
+            datagram(7, 2, 3, b"\r\nEND\r\n"),
+        ];
+        let parsed = parse_udp_response(&[&parts[0], &parts[1]]);
+        assert_eq!(parsed.missing_datagrams, 1);
+        assert!(!parsed.complete);
+        // the payload stops at the gap
+        assert_eq!(parsed.payload, b"VALUE a 0 6\r\n");
+    }
+
+    #[test]
+    fn later_larger_total_counts_the_gap_as_missing() {
+        let parts = [
+            datagram(7, 0, 1, b"VALUE a 0 6\r\n"),
+            datagram(7, 2, 3, b"\r\nEND\r\n"),
+        ];
+        let parsed = parse_udp_response(&[&parts[0], &parts[1]]);
+        assert_eq!(parsed.missing_datagrams, 1);
+        assert!(!parsed.complete);
+    }
+
+    #[test]
+    fn skips_datagrams_of_a_foreign_request() {
+        let parts = [
+            datagram(7, 0, 1, b"END\r\n"),
+            datagram(8, 0, 1, b"VALUE a 0 3\r\nfoo\r\nEND\r\n"),
+        ];
+        let parsed = parse_udp_response(&[&parts[0], &parts[1]]);
+        assert_eq!(parsed.request_id, 7);
+        assert!(parsed.complete);
+        assert_eq!(parsed.payload, b"END\r\n");
+    }
+}
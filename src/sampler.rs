@@ -0,0 +1,176 @@
+use crate::sizes::SizeSampler;
+use clap::ValueEnum;
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_distr::Zipf as ZipfDistribution;
+use std::collections::BTreeMap;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyDistribution {
+    Uniform,
+    Zipf,
+    Sequential,
+}
+
+// picks the index of the next key to request out of `len` keys
+pub trait KeySampler {
+    fn sample(&mut self, rng: &mut dyn RngCore) -> usize;
+}
+
+pub struct Uniform {
+    len: usize,
+}
+
+impl KeySampler for Uniform {
+    fn sample(&mut self, rng: &mut dyn RngCore) -> usize {
+        rng.gen_range(0..self.len)
+    }
+}
+
+// rank 1 is the hottest key, ranks are shifted down to zero-based indices
+pub struct Zipf {
+    distribution: ZipfDistribution<f64>,
+}
+
+impl KeySampler for Zipf {
+    fn sample(&mut self, rng: &mut dyn RngCore) -> usize {
+        self.distribution.sample(rng) as usize - 1
+    }
+}
+
+// walks the key space in order and wraps around, every thread has its own cursor
+pub struct Sequential {
+    len: usize,
+    next: usize,
+}
+
+impl KeySampler for Sequential {
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> usize {
+        let index = self.next;
+        self.next = (self.next + 1) % self.len;
+        index
+    }
+}
+
+pub fn new_key_sampler(
+    distribution: KeyDistribution,
+    len: usize,
+    zipf_exponent: f64,
+) -> Result<Box<dyn KeySampler + Send>, String> {
+    match distribution {
+        KeyDistribution::Uniform => Ok(Box::new(Uniform { len })),
+        KeyDistribution::Zipf => {
+            let distribution = ZipfDistribution::new(len as u64, zipf_exponent).map_err(|_| {
+                format!(
+                    "invalid zipf distribution over {} keys with exponent {}",
+                    len, zipf_exponent
+                )
+            })?;
+            Ok(Box::new(Zipf { distribution }))
+        }
+        KeyDistribution::Sequential => Ok(Box::new(Sequential { len, next: 0 })),
+    }
+}
+
+// --hot-key-fraction/--hot-key-ratio: `ratio` of the requests go to a fixed random subset
+// of the key space, the rest are left to the wrapped sampler
+pub struct HotSpot {
+    inner: Box<dyn KeySampler + Send>,
+    hot: Vec<usize>,
+    ratio: f64,
+}
+
+impl KeySampler for HotSpot {
+    fn sample(&mut self, rng: &mut dyn RngCore) -> usize {
+        if !self.hot.is_empty() && rng.gen::<f64>() < self.ratio {
+            self.hot[rng.gen_range(0..self.hot.len())]
+        } else {
+            self.inner.sample(rng)
+        }
+    }
+}
+
+// the hot subset is drawn from `seed` alone, so every thread shares the same hot keys
+pub fn with_hot_keys(
+    inner: Box<dyn KeySampler + Send>,
+    len: usize,
+    fraction: f64,
+    ratio: f64,
+    seed: u64,
+) -> Box<dyn KeySampler + Send> {
+    let hot_len = (len as f64 * fraction) as usize;
+    let hot = rand::seq::index::sample(&mut StdRng::seed_from_u64(seed), len, hot_len).into_vec();
+    Box::new(HotSpot { inner, hot, ratio })
+}
+
+// --trace-file: replays recorded key indices in order and wraps around at the end
+pub struct Trace {
+    indices: Vec<usize>,
+    next: usize,
+}
+
+impl KeySampler for Trace {
+    fn sample(&mut self, _rng: &mut dyn RngCore) -> usize {
+        let index = self.indices[self.next];
+        self.next = (self.next + 1) % self.indices.len();
+        index
+    }
+}
+
+// `indices` must not be empty
+pub fn new_trace_sampler(indices: Vec<usize>) -> Box<dyn KeySampler + Send> {
+    Box::new(Trace { indices, next: 0 })
+}
+
+struct SizeBucket {
+    size: usize,
+    indices: Vec<usize>,
+    sampler: Box<dyn KeySampler + Send>,
+}
+
+// --key-size-distribution: draws a key size for every request, then picks a key of the
+// nearest size with the key distribution of that size's keys
+pub struct BySize {
+    sizes: SizeSampler,
+    buckets: Vec<SizeBucket>,
+}
+
+impl KeySampler for BySize {
+    fn sample(&mut self, mut rng: &mut dyn RngCore) -> usize {
+        let size = self.sizes.sample(&mut rng);
+        let mut i = self.buckets.partition_point(|bucket| bucket.size < size);
+        if i == self.buckets.len()
+            || (i > 0 && size - self.buckets[i - 1].size < self.buckets[i].size - size)
+        {
+            i -= 1;
+        }
+        let bucket = &mut self.buckets[i];
+        bucket.indices[bucket.sampler.sample(rng)]
+    }
+}
+
+// `key_sizes` holds the size of every key, it must not be empty
+pub fn by_key_size(
+    distribution: KeyDistribution,
+    zipf_exponent: f64,
+    key_sizes: &[usize],
+    sizes: SizeSampler,
+) -> Result<Box<dyn KeySampler + Send>, String> {
+    let mut by_size: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, &size) in key_sizes.iter().enumerate() {
+        by_size.entry(size).or_default().push(index);
+    }
+    let buckets = by_size
+        .into_iter()
+        .map(|(size, indices)| {
+            let sampler = new_key_sampler(distribution, indices.len(), zipf_exponent)?;
+            Ok(SizeBucket {
+                size,
+                indices,
+                sampler,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Box::new(BySize { sizes, buckets }))
+}
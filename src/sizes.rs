@@ -0,0 +1,79 @@
+use clap::ValueEnum;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal};
+use std::ops::RangeInclusive;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SizeDistribution {
+    Uniform,
+    Normal,
+    Lognormal,
+}
+
+// draws the length of every generated key or value. normal and lognormal draws are rounded and
+// clamped into `sizes`, uniform draws from all of it
+pub enum SizeSampler {
+    Uniform(RangeInclusive<usize>),
+    Normal(Normal<f64>, RangeInclusive<usize>),
+    LogNormal(LogNormal<f64>, RangeInclusive<usize>),
+}
+
+impl SizeSampler {
+    // `mean` and `stddev` describe the sizes themselves, for lognormal as well: they are
+    // turned into the mu and sigma of the underlying normal distribution
+    pub fn new(
+        distribution: SizeDistribution,
+        sizes: RangeInclusive<usize>,
+        mean: f64,
+        stddev: f64,
+    ) -> Result<Self, String> {
+        let invalid = |e| {
+            format!(
+                "invalid {} size distribution with mean {} and stddev {}: {}",
+                format!("{:?}", distribution).to_lowercase(),
+                mean,
+                stddev,
+                e
+            )
+        };
+        match distribution {
+            SizeDistribution::Uniform => Ok(SizeSampler::Uniform(sizes)),
+            SizeDistribution::Normal => Ok(SizeSampler::Normal(
+                Normal::new(mean, stddev).map_err(invalid)?,
+                sizes,
+            )),
+            SizeDistribution::Lognormal => {
+                if mean <= 0.0 {
+                    return Err(invalid(rand_distr::NormalError::MeanTooSmall));
+                }
+                let sigma2 = (1.0 + (stddev / mean).powi(2)).ln();
+                Ok(SizeSampler::LogNormal(
+                    LogNormal::new(mean.ln() - sigma2 / 2.0, sigma2.sqrt()).map_err(invalid)?,
+                    sizes,
+                ))
+            }
+        }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        let clamp = |size: f64, sizes: &RangeInclusive<usize>| {
+            (size.round() as usize).clamp(*sizes.start(), *sizes.end())
+        };
+        match self {
+            // a fixed size draws nothing, so the dataset of a seed stays the same without a range
+            SizeSampler::Uniform(sizes) if sizes.start() == sizes.end() => *sizes.start(),
+            SizeSampler::Uniform(sizes) => rng.gen_range(sizes.clone()),
+            SizeSampler::Normal(normal, sizes) => clamp(normal.sample(rng), sizes),
+            SizeSampler::LogNormal(lognormal, sizes) => clamp(lognormal.sample(rng), sizes),
+        }
+    }
+
+    // every size `sample` can return
+    pub fn sizes(&self) -> &RangeInclusive<usize> {
+        match self {
+            SizeSampler::Uniform(sizes)
+            | SizeSampler::Normal(_, sizes)
+            | SizeSampler::LogNormal(_, sizes) => sizes,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+use trust_dns_resolver::TokioAsyncResolver;
+
+// one memcached instance registered in dns
+pub struct SrvServer {
+    // the resolved address of the target
+    pub host: String,
+    // the target name, what a TLS server certificate carries
+    pub target: String,
+    pub port: u16,
+    priority: u16,
+    weight: u16,
+}
+
+// --srv-record: looks up _memcache._<transport>.<name> and resolves every target to an
+// address. only the records of the lowest priority are used, the others are fallbacks, and
+// they are ordered by weight the way RFC 2782 picks them, drawn from `seed`
+pub async fn resolve(
+    name: &str,
+    transport: &str,
+    seed: u64,
+) -> Result<Vec<SrvServer>, Box<dyn Error>> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+    let service = format!("_memcache._{}.{}", transport, name.trim_end_matches('.'));
+    let lookup = resolver
+        .srv_lookup(service.as_str())
+        .await
+        .map_err(|e| format!("{}: {}", service, e))?;
+
+    let mut records = vec![];
+    for srv in lookup.iter() {
+        let target = srv.target().to_utf8();
+        let ip = resolver
+            .lookup_ip(target.as_str())
+            .await
+            .map_err(|e| format!("{} of {}: {}", target, service, e))?
+            .iter()
+            .next()
+            .ok_or_else(|| format!("{} of {} has no address", target, service))?;
+        records.push(SrvServer {
+            host: ip.to_string(),
+            target: target.trim_end_matches('.').to_string(),
+            port: srv.port(),
+            priority: srv.priority(),
+            weight: srv.weight(),
+        });
+    }
+    let Some(priority) = records.iter().map(|record| record.priority).min() else {
+        return Err(format!("{} has no records", service).into());
+    };
+    records.retain(|record| record.priority == priority);
+
+    // each pick takes a record with a chance of its share of the remaining weight, zero
+    // weights only get picked once every weighted record is gone
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut servers = vec![];
+    while !records.is_empty() {
+        let total: u32 = records.iter().map(|record| record.weight as u32).sum();
+        let pick = match total {
+            0 => 0,
+            total => {
+                let mut threshold = rng.gen_range(1..=total);
+                records
+                    .iter()
+                    .position(|record| {
+                        let weight = record.weight as u32;
+                        if threshold <= weight {
+                            return true;
+                        }
+                        threshold -= weight;
+                        false
+                    })
+                    .unwrap_or_default()
+            }
+        };
+        servers.push(records.remove(pick));
+    }
+    Ok(servers)
+}
+
+// the indices of `servers` in the order the requests rotate over them, every server appears
+// in proportion to its weight, spread out the way smooth weighted round-robin does it. zero
+// weights get no requests, unless every weight is zero
+pub fn schedule(servers: &[SrvServer]) -> Vec<usize> {
+    let mut weights: Vec<u64> = servers.iter().map(|server| server.weight as u64).collect();
+    if weights.iter().all(|&weight| weight == 0) {
+        weights.iter_mut().for_each(|weight| *weight = 1);
+    }
+    let divisor = weights.iter().fold(0, |a, &b| gcd(a, b));
+    weights.iter_mut().for_each(|weight| *weight /= divisor);
+    let total: u64 = weights.iter().sum();
+
+    let mut current = vec![0i64; weights.len()];
+    let mut schedule = Vec::with_capacity(total as usize);
+    for _ in 0..total {
+        for (current, &weight) in current.iter_mut().zip(weights.iter()) {
+            *current += weight as i64;
+        }
+        let pick = (0..current.len())
+            .max_by_key(|&i| (current[i], std::cmp::Reverse(i)))
+            .unwrap_or_default();
+        current[pick] -= total as i64;
+        schedule.push(pick);
+    }
+    schedule
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        b => gcd(b, a % b),
+    }
+}
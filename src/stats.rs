@@ -0,0 +1,449 @@
+use crate::metrics::Metrics;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// per-operation latencies of one benchmark thread, in nanoseconds
+//
+// a latency is taken in socket_task with Instant from just before the request is sent until
+// its reply arrived, so time a request spends queued in the mpsc channel is not included.
+// timed out and failed requests leave no sample, they are counted as errors instead.
+// without --pipeline-depth a socket task has a single request in flight, which makes
+// these round-trip times of one closed-loop client rather than the queueing delay of the
+// open-loop sender. with it, every request of a pipelined batch is timed from when the
+// batch was written, so a reply also waits behind the ones ahead of it. a --batch-size
+// multi-get is one sample that covers the server reading all of its keys.
+#[derive(Default)]
+pub struct LatencyRecorder {
+    samples: Vec<u64>,
+    // running mean and sum of squared deviations (Welford), so the spread needs no extra pass
+    mean: f64,
+    m2: f64,
+}
+
+impl LatencyRecorder {
+    pub fn record(&mut self, latency: Duration) {
+        let ns = latency.as_nanos() as u64;
+        self.samples.push(ns);
+
+        let delta = ns as f64 - self.mean;
+        self.mean += delta / self.samples.len() as f64;
+        self.m2 += delta * (ns as f64 - self.mean);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn samples(&self) -> &[u64] {
+        &self.samples
+    }
+
+    // combines the running moments of both halves (Chan et al.) before joining the samples
+    pub fn merge(&mut self, other: &LatencyRecorder) {
+        let (n_a, n_b) = (self.samples.len() as f64, other.samples.len() as f64);
+        if n_b > 0.0 {
+            let n = n_a + n_b;
+            let delta = other.mean - self.mean;
+            self.mean += delta * n_b / n;
+            self.m2 += other.m2 + delta * delta * n_a * n_b / n;
+        }
+        self.samples.extend_from_slice(&other.samples);
+    }
+
+    // population standard deviation in nanoseconds
+    pub fn stddev(&self) -> f64 {
+        match self.samples.len() {
+            0 => 0.0,
+            n => (self.m2 / n as f64).sqrt(),
+        }
+    }
+
+    pub fn percentiles(&self) -> Percentiles {
+        Percentiles {
+            stddev: self.stddev() as u64,
+            ..compute_percentiles(&self.samples)
+        }
+    }
+
+    pub fn print(&self, out: &mut impl Write, label: &str) -> io::Result<()> {
+        let p = self.percentiles();
+        if p.count == 0 {
+            return writeln!(out, "{} latency: no samples", label);
+        }
+
+        // coefficient of variation: spread relative to the mean, lower is more consistent
+        let cv = p.stddev as f64 / p.mean.max(1) as f64;
+        writeln!(
+            out,
+            "{} latency: min {:?}, mean {:?} ± {:?} (cv {:.2}), p50 {:?}, p95 {:?}, p99 {:?}, p999 {:?}, max {:?}",
+            label,
+            Duration::from_nanos(p.min),
+            Duration::from_nanos(p.mean),
+            Duration::from_nanos(p.stddev),
+            cv,
+            Duration::from_nanos(p.p50),
+            Duration::from_nanos(p.p95),
+            Duration::from_nanos(p.p99),
+            Duration::from_nanos(p.p999),
+            Duration::from_nanos(p.max),
+        )
+    }
+}
+
+// latency summary in nanoseconds, all zero when there were no samples.
+// stddev is only filled in by LatencyRecorder::percentiles
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Percentiles {
+    pub count: usize,
+    pub min: u64,
+    pub mean: u64,
+    pub stddev: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+pub fn compute_percentiles(latencies: &[u64]) -> Percentiles {
+    if latencies.is_empty() {
+        return Percentiles::default();
+    }
+
+    let mut sorted = latencies.to_vec();
+    sorted.sort_unstable();
+
+    Percentiles {
+        count: sorted.len(),
+        min: sorted[0],
+        mean: sorted.iter().sum::<u64>() / sorted.len() as u64,
+        stddev: 0,
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+        p999: percentile(&sorted, 99.9),
+        max: sorted[sorted.len() - 1],
+    }
+}
+
+// nearest-rank percentile of an already sorted, non-empty slice
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// requests left without a reply, split by cause: a timeout means the request or its
+// reply was lost on the way (or is too late), a socket error means the receive failed
+#[derive(Default)]
+pub struct PacketLossTracker {
+    timeouts: usize,
+    socket_errors: usize,
+}
+
+impl PacketLossTracker {
+    pub fn record_timeout(&mut self) {
+        self.timeouts += 1;
+    }
+
+    pub fn record_socket_error(&mut self) {
+        self.socket_errors += 1;
+    }
+
+    pub fn timeouts(&self) -> usize {
+        self.timeouts
+    }
+
+    pub fn socket_errors(&self) -> usize {
+        self.socket_errors
+    }
+
+    pub fn merge(&mut self, other: &PacketLossTracker) {
+        self.timeouts += other.timeouts;
+        self.socket_errors += other.socket_errors;
+    }
+
+    // fraction of `requests` whose reply never arrived
+    pub fn loss_rate(&self, requests: u64) -> f64 {
+        match requests {
+            0 => 0.0,
+            requests => self.timeouts as f64 / requests as f64,
+        }
+    }
+}
+
+// retrieval replies of a pass, shared by every socket task: a VALUE block is a hit,
+// a requested key missing from the reply is a miss
+#[derive(Default, Clone)]
+pub struct HitCounter {
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    // keys of every retrieval put on the wire, answered or not, which the server's cmd_get
+    // counts as well
+    sent: Arc<AtomicU64>,
+}
+
+impl HitCounter {
+    pub fn record(&self, hits: u64, misses: u64) {
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+        self.misses.fetch_add(misses, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, keys: u64) {
+        self.sent.fetch_add(keys, Ordering::Relaxed);
+    }
+
+    pub fn merge(&self, other: &HitCounter) {
+        self.record(other.hits(), other.misses());
+        self.record_sent(other.sent());
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    // None when no retrieval was issued
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.hits() + self.misses() {
+            0 => None,
+            lookups => Some(self.hits() as f64 / lookups as f64),
+        }
+    }
+}
+
+// memcached protocol bytes written and read by every socket task of a pass, without
+// the UDP frame header so both protocols count the same payload
+#[derive(Default, Clone)]
+pub struct ByteCounter {
+    bytes_sent: Arc<AtomicU64>,
+    bytes_received: Arc<AtomicU64>,
+    // --metrics-port: exported as they are counted
+    metrics: Option<Metrics>,
+}
+
+impl ByteCounter {
+    pub fn with_metrics(metrics: Option<Metrics>) -> Self {
+        ByteCounter {
+            metrics,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_sent(bytes);
+        }
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_received(bytes);
+        }
+    }
+
+    // totals only, the merged bytes were exported when they were counted
+    pub fn merge(&self, other: &ByteCounter) {
+        self.bytes_sent.fetch_add(other.sent(), Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(other.received(), Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    // both directions together, in megabytes (10^6 bytes) per second
+    pub fn mb_per_sec(&self, duration: Duration) -> f64 {
+        (self.sent() + self.received()) as f64 / 1e6 / duration.as_secs_f64()
+    }
+}
+
+// why a request failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    // no reply within --recv-timeout-ms
+    Timeout,
+    // the request could not be written, even after --max-retries
+    SendError,
+    // the socket failed or the stream closed while reading the reply
+    RecvError,
+    // a reply without a status word, e.g. a truncated datagram
+    ParseError,
+    // ERROR, CLIENT_ERROR or SERVER_ERROR from the server
+    ErrorReply,
+    // --validate: a get returned a different value than the dataset holds
+    ValidationMismatch,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 6] = [
+        ErrorKind::Timeout,
+        ErrorKind::SendError,
+        ErrorKind::RecvError,
+        ErrorKind::ParseError,
+        ErrorKind::ErrorReply,
+        ErrorKind::ValidationMismatch,
+    ];
+
+    fn index(self) -> usize {
+        ErrorKind::ALL
+            .iter()
+            .position(|&k| k == self)
+            .unwrap_or_default()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::SendError => "send_error",
+            ErrorKind::RecvError => "recv_error",
+            ErrorKind::ParseError => "parse_error",
+            ErrorKind::ErrorReply => "error_reply",
+            ErrorKind::ValidationMismatch => "validation_mismatch",
+        }
+    }
+}
+
+// run-wide counters shared through an Arc by every socket task. they are updated with
+// relaxed atomics on every reply, so the --interval-ms reporter can read them mid-pass
+pub struct BenchmarkStats {
+    ops: AtomicU64,
+    errors: AtomicU64,
+    // one counter per ErrorKind, in the order of ErrorKind::ALL
+    error_kinds: [AtomicU64; ErrorKind::ALL.len()],
+    // sum over every reply, for the mean latency of an interval
+    latency_ns: AtomicU64,
+    min_latency_ns: AtomicU64,
+    max_latency_ns: AtomicU64,
+    // --metrics-port: exported as they are recorded
+    metrics: Option<Metrics>,
+}
+
+impl Default for BenchmarkStats {
+    fn default() -> Self {
+        BenchmarkStats {
+            ops: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            error_kinds: Default::default(),
+            latency_ns: AtomicU64::new(0),
+            min_latency_ns: AtomicU64::new(u64::MAX),
+            max_latency_ns: AtomicU64::new(0),
+            metrics: None,
+        }
+    }
+}
+
+impl BenchmarkStats {
+    pub fn with_metrics(metrics: Option<Metrics>) -> Self {
+        BenchmarkStats {
+            metrics,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_reply(&self, latency: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_reply(latency);
+        }
+        let ns = latency.as_nanos() as u64;
+        self.ops.fetch_add(1, Ordering::Relaxed);
+        self.latency_ns.fetch_add(ns, Ordering::Relaxed);
+
+        let mut current = self.min_latency_ns.load(Ordering::Relaxed);
+        while ns < current {
+            match self.min_latency_ns.compare_exchange_weak(
+                current,
+                ns,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+
+        let mut current = self.max_latency_ns.load(Ordering::Relaxed);
+        while ns > current {
+            match self.max_latency_ns.compare_exchange_weak(
+                current,
+                ns,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    // failed requests: send failures, receive timeouts and error replies
+    pub fn record_error(&self, kind: ErrorKind) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.record_error_kind(kind);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_error();
+        }
+    }
+
+    // a mismatching value still got its reply, so it is no error of the request itself
+    pub fn record_validation_mismatch(&self) {
+        self.record_error_kind(ErrorKind::ValidationMismatch);
+    }
+
+    fn record_error_kind(&self, kind: ErrorKind) {
+        self.error_kinds[kind.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn error_count(&self, kind: ErrorKind) -> u64 {
+        self.error_kinds[kind.index()].load(Ordering::Relaxed)
+    }
+
+    // every ErrorKind with its count
+    pub fn error_kinds(&self) -> Vec<(ErrorKind, u64)> {
+        ErrorKind::ALL
+            .iter()
+            .map(|&kind| (kind, self.error_count(kind)))
+            .collect()
+    }
+
+    pub fn ops(&self) -> u64 {
+        self.ops.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn latency_ns(&self) -> u64 {
+        self.latency_ns.load(Ordering::Relaxed)
+    }
+
+    // None until the first reply was recorded
+    pub fn min_latency(&self) -> Option<Duration> {
+        match self.min_latency_ns.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            ns => Some(Duration::from_nanos(ns)),
+        }
+    }
+
+    pub fn max_latency(&self) -> Option<Duration> {
+        self.min_latency()
+            .map(|_| Duration::from_nanos(self.max_latency_ns.load(Ordering::Relaxed)))
+    }
+}
@@ -0,0 +1,161 @@
+use crate::output::RunsSummary;
+use crate::stats::Percentiles;
+use crate::{TaskStats, ThreadStats};
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{Cell, CellAlignment, Color, ColumnConstraint, Table};
+use std::time::Duration;
+
+// colors of the values in a table. disabled, every cell stays plain
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub enabled: bool,
+    // --latency-warn-ms and --latency-critical-ms
+    pub latency_warn: Duration,
+    pub latency_critical: Duration,
+}
+
+impl Palette {
+    pub fn plain(&self, text: impl ToString) -> Cell {
+        Cell::new(text.to_string())
+    }
+
+    pub fn throughput(&self, text: impl ToString) -> Cell {
+        self.colored(text, Color::Green)
+    }
+
+    // red as soon as a single request failed
+    pub fn errors(&self, errors: u64, text: impl ToString) -> Cell {
+        match errors {
+            0 => self.colored(text, Color::Green),
+            _ => self.colored(text, Color::Red),
+        }
+    }
+
+    pub fn latency(&self, latency: Duration) -> Cell {
+        let color = match latency {
+            l if l >= self.latency_critical => Color::Red,
+            l if l >= self.latency_warn => Color::Yellow,
+            _ => Color::Green,
+        };
+        self.colored(format!("{:?}", latency), color)
+    }
+
+    fn colored(&self, text: impl ToString, color: Color) -> Cell {
+        match self.enabled {
+            true => Cell::new(text.to_string()).fg(color),
+            false => Cell::new(text.to_string()),
+        }
+    }
+
+    fn new_table(&self) -> Table {
+        let mut table = Table::new();
+        table.load_style(UTF8_FULL_CONDENSED);
+        // the caller decides whether to color, not comfy-table's own tty check
+        if self.enabled {
+            table.enforce_styling();
+        }
+        table
+    }
+}
+
+// --output-format table: one column per thread plus the pass total
+pub fn pass_table(
+    label: &str,
+    threads: &[ThreadStats],
+    total: &TaskStats,
+    palette: &Palette,
+) -> Table {
+    let mut header = vec![label.to_string()];
+    header.extend(threads.iter().map(|t| format!("thread {}", t.thread)));
+    header.push("total".to_string());
+
+    // percentiles sort every sample, so they are taken once per column
+    let columns: Vec<(&TaskStats, Percentiles)> = threads
+        .iter()
+        .map(|t| &t.stats)
+        .chain(std::iter::once(total))
+        .map(|stats| (stats, stats.latencies.percentiles()))
+        .collect();
+    let row = |name: &str, value: &dyn Fn(&TaskStats, &Percentiles) -> Cell| {
+        let mut row = vec![Cell::new(name)];
+        row.extend(columns.iter().map(|(stats, p)| value(stats, p)));
+        row
+    };
+    let latency = |ns: u64| palette.latency(Duration::from_nanos(ns));
+
+    let mut table = palette.new_table();
+    table.set_header(header);
+    table.add_row(row("ops", &|s, _| palette.plain(s.latencies.len())));
+    table.add_row(row("duration", &|s, _| {
+        palette.plain(format!("{:?}", s.duration))
+    }));
+    table.add_row(row("ops/sec", &|s, _| {
+        palette.throughput(format!(
+            "{:.2}",
+            s.latencies.len() as f64 / s.duration.as_secs_f64()
+        ))
+    }));
+    table.add_row(row("errors", &|s, _| {
+        palette.errors(
+            s.errors as u64,
+            format!("{} ({:.2}%)", s.errors, s.error_rate() * 100.0),
+        )
+    }));
+    table.add_row(row("mean", &|_, p| {
+        palette.plain(format!(
+            "{:?} ± {:?}",
+            Duration::from_nanos(p.mean),
+            Duration::from_nanos(p.stddev)
+        ))
+    }));
+    table.add_row(row("p50", &|_, p| latency(p.p50)));
+    table.add_row(row("p95", &|_, p| latency(p.p95)));
+    table.add_row(row("p99", &|_, p| latency(p.p99)));
+    table.add_row(row("max", &|_, p| latency(p.max)));
+    align_values(&mut table);
+    table
+}
+
+// --output-format table: two columns, metric name and value
+pub fn summary_table(rows: Vec<(String, Cell)>, palette: &Palette) -> Table {
+    let mut table = palette.new_table();
+    table.set_header(vec!["metric", "value"]);
+    for (name, value) in rows {
+        table.add_row(vec![Cell::new(name), value]);
+    }
+    align_values(&mut table);
+    table
+}
+
+// --compare-protocols: one column per protocol, the mean over its runs
+pub fn comparison_table(columns: &[(String, &RunsSummary)], palette: &Palette) -> Table {
+    let mut header = vec!["metric".to_string()];
+    header.extend(columns.iter().map(|(name, _)| name.clone()));
+    let row = |name: &str, value: &dyn Fn(&RunsSummary) -> Cell| {
+        let mut row = vec![Cell::new(name)];
+        row.extend(columns.iter().map(|(_, runs)| value(runs)));
+        row
+    };
+
+    let mut table = palette.new_table();
+    table.set_header(header);
+    table.add_row(row("ops/sec", &|r| {
+        palette.throughput(format!("{:.2}", r.ops_per_sec.mean))
+    }));
+    table.add_row(row("p99", &|r| {
+        palette.latency(Duration::from_secs_f64(r.p99_us.mean / 1e6))
+    }));
+    table.add_row(row("error rate", &|r| {
+        palette.plain(format!("{:.2}%", r.error_rate.mean * 100.0))
+    }));
+    align_values(&mut table);
+    table
+}
+
+// names stay left, every value column is right aligned and never wrapped
+fn align_values(table: &mut Table) {
+    for column in table.column_iter_mut().skip(1) {
+        column.set_cell_alignment(CellAlignment::Right);
+        column.set_constraint(ColumnConstraint::ContentWidth);
+    }
+}
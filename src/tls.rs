@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+// -l tls: the server certificate is checked against --tls-ca-cert, or the webpki roots
+// without it. --tls-client-cert and --tls-client-key authenticate the benchmark itself
+pub fn client_config(
+    ca_cert: Option<&Path>,
+    client_cert: Option<&Path>,
+    client_key: Option<&Path>,
+) -> Result<Arc<ClientConfig>, Box<dyn Error>> {
+    let pem_error = |path: &Path, e| format!("{}: {}", path.display(), e);
+
+    let mut roots = RootCertStore::empty();
+    match ca_cert {
+        Some(path) => {
+            for cert in CertificateDer::pem_file_iter(path).map_err(|e| pem_error(path, e))? {
+                roots.add(cert.map_err(|e| pem_error(path, e))?)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let config = match (client_cert, client_key) {
+        (Some(cert), Some(key)) => {
+            let chain = CertificateDer::pem_file_iter(cert)
+                .map_err(|e| pem_error(cert, e))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| pem_error(cert, e))?;
+            let key = PrivateKeyDer::from_pem_file(key).map_err(|e| pem_error(key, e))?;
+            builder.with_client_auth_cert(chain, key)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("--tls-client-cert and --tls-client-key go together".into()),
+    };
+    Ok(Arc::new(config))
+}
+
+// `addr` is host:port, the host is the name the server certificate has to carry
+pub async fn connect(
+    config: &Arc<ClientConfig>,
+    addr: &str,
+) -> std::io::Result<TlsStream<TcpStream>> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let stream = TcpStream::connect(addr).await?;
+    TlsConnector::from(Arc::clone(config))
+        .connect(name, stream)
+        .await
+}
@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::RngCore;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValuePattern {
+    Random,
+    Compressible,
+    Zeros,
+    // uniformly random bytes, not even valid utf-8, so nothing along the way can compress them
+    RandomBytes,
+}
+
+// builds the value stored under each key of the dataset
+pub trait ValueGenerator {
+    fn generate(&mut self, rng: &mut dyn RngCore, len: usize) -> Vec<u8>;
+}
+
+pub struct Random;
+
+impl ValueGenerator for Random {
+    fn generate(&mut self, rng: &mut dyn RngCore, len: usize) -> Vec<u8> {
+        Alphanumeric.sample_string(rng, len).into_bytes()
+    }
+}
+
+// a random 8-byte chunk repeated up to `len`, every value gets its own chunk
+pub struct Compressible;
+
+impl ValueGenerator for Compressible {
+    fn generate(&mut self, rng: &mut dyn RngCore, len: usize) -> Vec<u8> {
+        let chunk = Alphanumeric.sample_string(rng, 8);
+        chunk.bytes().cycle().take(len).collect()
+    }
+}
+
+pub struct Zeros;
+
+impl ValueGenerator for Zeros {
+    fn generate(&mut self, _rng: &mut dyn RngCore, len: usize) -> Vec<u8> {
+        vec![0; len]
+    }
+}
+
+pub struct RandomBytes;
+
+impl ValueGenerator for RandomBytes {
+    fn generate(&mut self, rng: &mut dyn RngCore, len: usize) -> Vec<u8> {
+        let mut value = vec![0; len];
+        rng.fill_bytes(&mut value);
+        value
+    }
+}
+
+// --fixed-value: the same string for every key, whatever the requested length
+pub struct Fixed {
+    value: String,
+}
+
+impl ValueGenerator for Fixed {
+    fn generate(&mut self, _rng: &mut dyn RngCore, _len: usize) -> Vec<u8> {
+        self.value.as_bytes().to_vec()
+    }
+}
+
+pub fn new_value_generator(
+    pattern: ValuePattern,
+    fixed_value: Option<&str>,
+) -> Box<dyn ValueGenerator> {
+    if let Some(value) = fixed_value {
+        return Box::new(Fixed {
+            value: value.to_string(),
+        });
+    }
+    match pattern {
+        ValuePattern::Random => Box::new(Random),
+        ValuePattern::Compressible => Box::new(Compressible),
+        ValuePattern::Zeros => Box::new(Zeros),
+        ValuePattern::RandomBytes => Box::new(RandomBytes),
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityHash {
+    Crc32,
+}
+
+// hex digits of the crc32 suffix
+const CRC32_SUFFIX_LEN: usize = 8;
+
+impl IntegrityHash {
+    // appends the hash of `value` to it as a hex suffix, so a reply can be checked on its own
+    pub fn append(&self, value: &mut Vec<u8>) {
+        match self {
+            IntegrityHash::Crc32 => {
+                let suffix = format!("{:08x}", crc32fast::hash(value));
+                value.extend_from_slice(suffix.as_bytes());
+            }
+        }
+    }
+
+    // whether the suffix of `value` is the hash of the payload before it
+    pub fn verify(&self, value: &[u8]) -> bool {
+        match self {
+            IntegrityHash::Crc32 => {
+                let Some(split) = value.len().checked_sub(CRC32_SUFFIX_LEN) else {
+                    return false;
+                };
+                let (payload, suffix) = value.split_at(split);
+                suffix == format!("{:08x}", crc32fast::hash(payload)).as_bytes()
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// where the results go: stdout, or the --output-file when one is given. clones write to
+// the same destination, so the --interval-ms reporter can stream lines into it
+#[derive(Clone)]
+pub struct Writer {
+    inner: Arc<Mutex<Box<dyn Write + Send>>>,
+    // false for an --append file that already had content, it has its csv header then
+    started_empty: bool,
+}
+
+impl Writer {
+    pub fn new(path: Option<&Path>, append: bool) -> io::Result<Writer> {
+        let (inner, started_empty): (Box<dyn Write + Send>, bool) = match path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(path)?;
+                let empty = file.metadata()?.len() == 0;
+                (Box::new(file), empty)
+            }
+            None => (Box::new(io::stdout()), true),
+        };
+        Ok(Writer {
+            inner: Arc::new(Mutex::new(inner)),
+            started_empty,
+        })
+    }
+
+    pub fn started_empty(&self) -> bool {
+        self.started_empty
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.lock().unwrap().write(buf)
+    }
+
+    // whole lines go out under one lock, so concurrent writers never interleave mid-line
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.lock().unwrap().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.lock().unwrap().flush()
+    }
+}